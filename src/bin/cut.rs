@@ -0,0 +1,35 @@
+use std::env;
+use std::fs;
+
+use main_error::MainError;
+use tf_demo_parser::demo::cut::cut_demo;
+use tf_demo_parser::demo::data::DemoTick;
+use tf_demo_parser::Demo;
+
+fn main() -> Result<(), MainError> {
+    #[cfg(feature = "better_panic")]
+    better_panic::install();
+
+    #[cfg(feature = "trace")]
+    tracing_subscriber::fmt::init();
+
+    let args: Vec<_> = env::args().collect();
+    if args.len() < 5 {
+        println!("4 arguments required: <input> <output> <start tick> <end tick>");
+        return Ok(());
+    }
+    let path = args[1].clone();
+    let out_path = args[2].clone();
+    let start: u32 = args[3].parse()?;
+    let end: u32 = args[4].parse()?;
+
+    let file = fs::read(path)?;
+    let demo = Demo::new(&file);
+    let cut = cut_demo(
+        demo.get_stream(),
+        DemoTick::from(start),
+        DemoTick::from(end),
+    )?;
+    fs::write(out_path, cut)?;
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+
+use bitbuffer::{BitRead, BitWrite, BitWriteStream, LittleEndian};
+
+use crate::demo::data::DemoTick;
+use crate::demo::header::Header;
+use crate::demo::message::packetentities::{
+    EntityId, PacketEntitiesMessage, PacketEntity, UpdateType,
+};
+use crate::demo::message::{Message, NetTickMessage};
+use crate::demo::packet::message::MessagePacket;
+use crate::demo::packet::stop::StopPacket;
+use crate::demo::packet::{Packet, PacketType};
+use crate::demo::parser::handler::BorrowMessageHandler;
+use crate::demo::parser::{DemoHandler, Encode, MessageHandler};
+use crate::demo::parser::{ParseError, RawPacketStream, Result};
+use crate::{MessageType, ParserState, Stream};
+
+/// Create a new demo containing only the ticks `[start, end]` of the source demo
+///
+/// The signon data, data tables and string tables are copied from the source demo,
+/// string table updates from before the cut are replayed at the start of the cut demo.
+/// Since the cut demo doesn't contain the entity updates from before `start`,
+/// the instance baselines and the full state of all entities at the cut point are written as delta-free
+/// entity messages in the first packet.
+///
+/// Ticks in the cut demo are rebased to start at 0, the header is updated to match the new length.
+///
+/// An error is returned if `start` is after `end` or if the source demo ends before `start`.
+pub fn cut_demo(mut stream: Stream, start: DemoTick, end: DemoTick) -> Result<Vec<u8>> {
+    if start > end {
+        return Err(ParseError::InvalidDemo(
+            "The start of the cut needs to be before the end",
+        ));
+    }
+
+    let mut header = Header::read(&mut stream)?;
+    let mut packets = RawPacketStream::new(stream.clone());
+    let mut handler = DemoHandler::parse_all_with_analyser(EntityTracker::default());
+
+    let mut body = Vec::new();
+    let mut out = BitWriteStream::new(&mut body, LittleEndian);
+
+    let mut carried_messages: Vec<Message> = Vec::new();
+    let mut last_net_tick: Option<NetTickMessage> = None;
+    let mut max_entries = 0;
+    let mut started = false;
+    let mut last_tick = DemoTick::default();
+    let mut frames = 0;
+    let mut packet_start = packets.pos();
+
+    while let Some(mut packet) = packets.next(&handler.state_handler)? {
+        let packet_end = packets.pos();
+        let packet_bits = stream.read_bits(packet_end - packet_start)?;
+        packet_start = packet_end;
+
+        match packet.packet_type() {
+            PacketType::Message | PacketType::UserCmd | PacketType::ConsoleCmd => {
+                let tick = packet.tick();
+                if tick > end {
+                    break;
+                }
+                if tick < start {
+                    if let Packet::Message(message_packet) = &packet {
+                        for message in &message_packet.messages {
+                            match message {
+                                Message::NetTick(net_tick) => {
+                                    last_net_tick = Some(net_tick.clone())
+                                }
+                                Message::PacketEntities(entities) => {
+                                    max_entries = entities.max_entries
+                                }
+                                Message::CreateStringTable(_)
+                                | Message::UpdateStringTable(_)
+                                | Message::SetConVar(_) => carried_messages.push(message.clone()),
+                                _ => {}
+                            }
+                        }
+                    }
+                    handler.handle_packet(packet)?;
+                    continue;
+                }
+
+                if !started {
+                    started = true;
+                    let mut messages: Vec<Message> = last_net_tick
+                        .take()
+                        .map(Message::NetTick)
+                        .into_iter()
+                        .collect();
+                    messages.append(&mut carried_messages);
+                    messages.extend(
+                        snapshot_messages(
+                            &handler.state_handler,
+                            handler.borrow_output(),
+                            max_entries,
+                        )
+                        .into_iter()
+                        .map(Message::PacketEntities),
+                    );
+                    Packet::Message(MessagePacket {
+                        tick: DemoTick::default(),
+                        messages,
+                        meta: Default::default(),
+                    })
+                    .encode(&mut out, &handler.state_handler)?;
+                    frames += 1;
+                }
+
+                // console commands are not needed for playback
+                if packet.packet_type() != PacketType::ConsoleCmd {
+                    last_tick = tick - start;
+                    packet.set_tick(last_tick);
+                    packet.encode(&mut out, &handler.state_handler)?;
+                    frames += 1;
+                }
+            }
+            PacketType::Stop => break,
+            _ if !started => {
+                packet_bits.write(&mut out)?;
+            }
+            _ => {
+                packet.set_tick(last_tick);
+                packet.encode(&mut out, &handler.state_handler)?;
+            }
+        }
+        handler.handle_packet(packet)?;
+    }
+
+    if !started {
+        return Err(ParseError::InvalidDemo(
+            "The start of the cut is past the end of the demo",
+        ));
+    }
+
+    Packet::Stop(StopPacket { tick: last_tick }).encode(&mut out, &handler.state_handler)?;
+
+    header.ticks = last_tick.into();
+    header.frames = frames;
+    header.duration = header.ticks as f32 * handler.state_handler.demo_meta.interval_per_tick;
+
+    let mut result = Vec::with_capacity(1072 + body.len());
+    header.write(&mut BitWriteStream::new(&mut result, LittleEndian))?;
+    result.extend_from_slice(&body);
+    Ok(result)
+}
+
+/// Build the delta-free entity messages that recreate the instance baselines and entity state
+///
+/// The first two messages each fill one of the instance baselines, the last contains the full state of every entity.
+fn snapshot_messages(
+    state: &ParserState,
+    entities: &BTreeMap<EntityId, PacketEntity>,
+    max_entries: u16,
+) -> Vec<PacketEntitiesMessage> {
+    let mut messages = Vec::with_capacity(3);
+
+    // a message with `updated_base_line` copies the baseline at `base_line` into the other baseline
+    // before adding its entities, so the baseline at index 1 has to be written first
+    for (base_line, target) in [(0, 1), (1, 0)] {
+        let baseline = &state.instance_baselines[target];
+        let entities: Vec<PacketEntity> = baseline
+            .keys()
+            .filter_map(|id| baseline.get(id))
+            .map(|baseline_entity| baseline_entity.clone().into())
+            .collect();
+        if !entities.is_empty() {
+            messages.push(PacketEntitiesMessage {
+                entities,
+                removed_entities: Vec::new(),
+                max_entries,
+                delta: None,
+                base_line,
+                updated_base_line: true,
+            });
+        }
+    }
+
+    messages.push(PacketEntitiesMessage {
+        entities: entities.values().cloned().collect(),
+        removed_entities: Vec::new(),
+        max_entries,
+        delta: None,
+        base_line: 0,
+        updated_base_line: false,
+    });
+    messages
+}
+
+/// Track the full state of every entity, including the props from the baselines
+///
+/// Entities leaving the pvs are kept since the client still knows about them, only deleted entities are dropped.
+#[derive(Default)]
+struct EntityTracker {
+    entities: BTreeMap<EntityId, PacketEntity>,
+}
+
+impl MessageHandler for EntityTracker {
+    type Output = BTreeMap<EntityId, PacketEntity>;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        message_type == MessageType::PacketEntities
+    }
+
+    fn handle_message(&mut self, message: &Message, _tick: DemoTick, parser_state: &ParserState) {
+        if let Message::PacketEntities(message) = message {
+            if message.delta.is_none() {
+                self.entities.clear();
+            }
+            for removed in &message.removed_entities {
+                self.entities.remove(removed);
+            }
+            for entity in &message.entities {
+                match entity.update_type {
+                    UpdateType::Enter => {
+                        let props = entity.props(parser_state).collect();
+                        self.entities.insert(
+                            entity.entity_index,
+                            PacketEntity {
+                                props,
+                                delta: None,
+                                in_pvs: true,
+                                ..entity.clone()
+                            },
+                        );
+                    }
+                    UpdateType::Preserve => {
+                        if let Some(existing) = self.entities.get_mut(&entity.entity_index) {
+                            existing.apply_update(&entity.props);
+                        }
+                    }
+                    UpdateType::Leave => {
+                        if let Some(existing) = self.entities.get_mut(&entity.entity_index) {
+                            existing.in_pvs = false;
+                        }
+                    }
+                    UpdateType::Delete => {
+                        self.entities.remove(&entity.entity_index);
+                    }
+                }
+            }
+        }
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self::Output {
+        self.entities
+    }
+}
+
+impl BorrowMessageHandler for EntityTracker {
+    type BorrowedOutput<'a> = &'a BTreeMap<EntityId, PacketEntity>;
+
+    fn borrow_output<'a>(&'a self, _state: &ParserState) -> Self::BorrowedOutput<'a> {
+        &self.entities
+    }
+}
@@ -1,3 +1,4 @@
+pub mod steamid;
 pub mod userinfo;
 
 use bitbuffer::{BitRead, BitReadStream, BitWrite, BitWriteStream, Endianness};
@@ -6,6 +7,7 @@ use std::cmp::Ordering;
 use std::fmt::{Debug, Display, Formatter};
 use std::ops::{Add, Sub};
 
+pub use steamid::{FormattedSteamId, SteamId, SteamIdFormat, SteamIdKind};
 pub use userinfo::UserInfo;
 
 #[derive(Eq, PartialEq, Clone)]
@@ -0,0 +1,267 @@
+use bitbuffer::{BitWriteSized, BitWriteStream, Endianness};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
+pub use steamid_ng::SteamID;
+
+/// The format used when serializing a [`SteamId`] trough [`SteamId::formatted`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SteamIdFormat {
+    /// The steam id as stored in the demo, `[U:1:xxx]` for modern demos and `STEAM_0:x:xxx` for older ones
+    #[default]
+    Raw,
+    /// The 64 bit steam id, serialized as string to prevent precision loss in json parsers
+    SteamId64,
+    /// `[U:1:xxx]`
+    SteamId3,
+    /// `STEAM_1:x:xxx`
+    SteamId2,
+}
+
+/// The kind of player behind a [`SteamId`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SteamIdKind {
+    Player(SteamID),
+    Bot,
+    /// SourceTV or replay
+    Hltv,
+    /// A steam id that couldn't be parsed
+    Unknown,
+}
+
+/// The steam id of a player
+///
+/// Only ids of kind [`SteamIdKind::Player`] can be converted to the other formats,
+/// bots and SourceTV are formatted as the raw id in any format.
+///
+/// The raw id is serialized, use [`SteamId::formatted`] to serialize the id in a different format.
+/// The kind isn't part of the serialized id and is derived from the raw id when deserializing,
+/// which turns SourceTV into a bot, store [`SteamId::is_bot`] and [`SteamId::is_hltv`] next to the id
+/// where the kind needs to survive serialization.
+#[derive(Debug, Clone)]
+pub struct SteamId {
+    raw: String,
+    kind: SteamIdKind,
+}
+
+impl SteamId {
+    pub fn new(raw: &str, is_fake_player: bool, is_hltv: bool) -> Self {
+        let kind = if is_hltv {
+            SteamIdKind::Hltv
+        } else if is_fake_player {
+            SteamIdKind::Bot
+        } else {
+            Self::parse_kind(raw)
+        };
+        SteamId {
+            raw: raw.into(),
+            kind,
+        }
+    }
+
+    fn parse_kind(raw: &str) -> SteamIdKind {
+        if raw == "BOT" {
+            SteamIdKind::Bot
+        } else {
+            SteamID::try_from(raw)
+                .map(SteamIdKind::Player)
+                .unwrap_or(SteamIdKind::Unknown)
+        }
+    }
+
+    pub fn kind(&self) -> SteamIdKind {
+        self.kind
+    }
+
+    pub fn is_bot(&self) -> bool {
+        self.kind == SteamIdKind::Bot
+    }
+
+    pub fn is_hltv(&self) -> bool {
+        self.kind == SteamIdKind::Hltv
+    }
+
+    /// The steam id as stored in the demo
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn steam_id(&self) -> Option<SteamID> {
+        match self.kind {
+            SteamIdKind::Player(steam_id) => Some(steam_id),
+            _ => None,
+        }
+    }
+
+    pub fn steam_id64(&self) -> Option<u64> {
+        self.steam_id().map(u64::from)
+    }
+
+    pub fn steam_id3(&self) -> Option<String> {
+        self.steam_id().map(|steam_id| steam_id.steam3())
+    }
+
+    pub fn steam_id2(&self) -> Option<String> {
+        self.steam_id().map(|steam_id| steam_id.steam2())
+    }
+
+    /// Format the steam id, falling back to the raw id for bots, SourceTV and unknown ids
+    pub fn format(&self, format: SteamIdFormat) -> String {
+        match format {
+            SteamIdFormat::Raw => None,
+            SteamIdFormat::SteamId64 => self.steam_id64().map(|id| id.to_string()),
+            SteamIdFormat::SteamId3 => self.steam_id3(),
+            SteamIdFormat::SteamId2 => self.steam_id2(),
+        }
+        .unwrap_or_else(|| self.raw.clone())
+    }
+
+    /// Get a wrapper that serializes the steam id in the given format
+    pub fn formatted(&self, format: SteamIdFormat) -> FormattedSteamId<'_> {
+        FormattedSteamId {
+            steam_id: self,
+            format,
+        }
+    }
+}
+
+/// A steam id that serializes in a specific format, see [`SteamId::formatted`]
+#[derive(Debug, Clone, Copy)]
+pub struct FormattedSteamId<'a> {
+    steam_id: &'a SteamId,
+    format: SteamIdFormat,
+}
+
+impl Serialize for FormattedSteamId<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.steam_id.format(self.format))
+    }
+}
+
+impl Display for FormattedSteamId<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.steam_id.format(self.format))
+    }
+}
+
+impl From<&str> for SteamId {
+    fn from(raw: &str) -> Self {
+        SteamId {
+            raw: raw.into(),
+            kind: Self::parse_kind(raw),
+        }
+    }
+}
+
+impl Default for SteamId {
+    fn default() -> Self {
+        SteamId::from("")
+    }
+}
+
+impl<E: Endianness> BitWriteSized<E> for SteamId {
+    fn write_sized(&self, stream: &mut BitWriteStream<E>, len: usize) -> bitbuffer::Result<()> {
+        self.raw.write_sized(stream, len)
+    }
+}
+
+impl Display for SteamId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+/// Steam ids of players are equal if they refer to the same account, regardless of the format,
+/// other ids are equal if the raw ids are equal
+impl PartialEq for SteamId {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.steam_id(), other.steam_id()) {
+            (Some(a), Some(b)) => a == b,
+            (None, None) => self.raw == other.raw,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for SteamId {}
+
+impl Hash for SteamId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.steam_id() {
+            Some(steam_id) => steam_id.hash(state),
+            None => self.raw.hash(state),
+        }
+    }
+}
+
+impl Serialize for SteamId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+impl<'de> Deserialize<'de> for SteamId {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = String::deserialize(deserializer)?;
+        Ok(SteamId::from(raw.as_str()))
+    }
+}
+
+#[test]
+fn test_steam_id_formats() {
+    let steam_id = SteamId::new("[U:1:64229260]", false, false);
+    assert_eq!(Some(76561198024494988), steam_id.steam_id64());
+    assert_eq!(Some("STEAM_1:0:32114630".to_string()), steam_id.steam_id2());
+    assert_eq!(steam_id, SteamId::from("76561198024494988"));
+
+    let bot = SteamId::new("BOT", true, false);
+    assert!(bot.is_bot());
+    assert_eq!(None, bot.steam_id64());
+    assert_eq!("BOT", bot.format(SteamIdFormat::SteamId64));
+    assert!(SteamId::new("BOT", true, true).is_hltv());
+}
+
+#[test]
+fn test_steam_id_serialize() {
+    let steam_id = SteamId::new("[U:1:64229260]", false, false);
+    assert_eq!(
+        r#""[U:1:64229260]""#,
+        serde_json::to_string(&steam_id).unwrap()
+    );
+    assert_eq!(
+        r#""76561198024494988""#,
+        serde_json::to_string(&steam_id.formatted(SteamIdFormat::SteamId64)).unwrap()
+    );
+    assert_eq!(
+        "STEAM_1:0:32114630",
+        steam_id.formatted(SteamIdFormat::SteamId2).to_string()
+    );
+
+    for steam_id in [
+        steam_id,
+        SteamId::from("76561198024494988"),
+        SteamId::new("BOT", true, false),
+        SteamId::new("", false, false),
+    ] {
+        let json = serde_json::to_string(&steam_id).unwrap();
+        let deserialized: SteamId = serde_json::from_str(&json).unwrap();
+        assert_eq!(steam_id.kind(), deserialized.kind());
+        assert_eq!(steam_id.as_str(), deserialized.as_str());
+    }
+
+    // the kind isn't serialized
+    let hltv = SteamId::new("BOT", true, true);
+    assert_eq!(r#""BOT""#, serde_json::to_string(&hltv).unwrap());
+    let deserialized: SteamId = serde_json::from_str(r#""BOT""#).unwrap();
+    assert_eq!(SteamIdKind::Bot, deserialized.kind());
+    assert_eq!(hltv, deserialized);
+}
@@ -1,3 +1,4 @@
+use crate::demo::data::SteamId;
 use crate::demo::message::packetentities::EntityId;
 use crate::demo::packet::stringtable::{ExtraData, StringTableEntry};
 use crate::demo::parser::analyser::UserId;
@@ -27,7 +28,7 @@ pub struct PlayerInfo {
     pub name: String,
     pub user_id: UserId,
     #[size = 32]
-    pub steam_id: String,
+    pub steam_id: SteamId,
     pub extra: u32, // all my sources say these 4 bytes don't exist
     pub friends_id: u32,
     pub friends_name_bytes: [u8; 32], // seem to all be 0 now
@@ -46,7 +47,11 @@ impl From<RawPlayerInfo> for PlayerInfo {
                 .trim_end_matches('\0')
                 .to_string(),
             user_id: raw.user_id.into(),
-            steam_id: raw.steam_id,
+            steam_id: SteamId::new(
+                &raw.steam_id,
+                raw.is_fake_player > 0,
+                raw.is_hl_tv > 0 || raw.is_replay > 0,
+            ),
             extra: raw.extra,
             friends_id: raw.friends_id,
             friends_name_bytes: raw.friends_name_bytes,
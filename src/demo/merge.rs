@@ -0,0 +1,122 @@
+use bitbuffer::{BitRead, BitWrite, BitWriteStream, LittleEndian};
+
+use crate::demo::data::DemoTick;
+use crate::demo::header::Header;
+use crate::demo::packet::datatable::DataTablePacket;
+use crate::demo::packet::stop::StopPacket;
+use crate::demo::packet::{Packet, PacketType};
+use crate::demo::parser::{DemoHandler, Encode, NullHandler, RawPacketStream, Result};
+use crate::{ParseError, Stream};
+
+/// Stitch consecutive demos of the same map together into a single demo
+///
+/// The packets of every demo are appended after the packets of the previous demo,
+/// with the ticks rebased to continue where the previous demo ended.
+/// If the data tables of a demo match the data tables of the first demo, its signon and data table packets are skipped,
+/// otherwise they are copied into the merged demo as is.
+/// The string tables are always kept, to update any entries that changed between the demos.
+///
+/// The header of the first demo is used for the merged demo, with the ticks, frames and duration updated.
+pub fn merge_demos<'a, I: IntoIterator<Item = Stream<'a>>>(streams: I) -> Result<Vec<u8>> {
+    let mut header: Option<Header> = None;
+    let mut data_tables: Option<DataTablePacket> = None;
+    let mut interval_per_tick = 0.0;
+    let mut offset = DemoTick::default();
+    let mut last_tick = DemoTick::default();
+    let mut frames = 0;
+    let mut handler = DemoHandler::parse_all_with_analyser(NullHandler);
+
+    let mut body = Vec::new();
+    let mut out = BitWriteStream::new(&mut body, LittleEndian);
+
+    for mut stream in streams {
+        let part_header = Header::read(&mut stream)?;
+        match &header {
+            Some(header) if header.map != part_header.map => {
+                return Err(ParseError::InvalidDemo(
+                    "Merged demos need to be recorded on the same map",
+                ));
+            }
+            Some(_) => {}
+            None => header = Some(part_header),
+        }
+
+        let mut packets = RawPacketStream::new(stream.clone());
+        let part_data_tables = read_data_tables(packets.clone())?;
+        let skip_signon = match (&data_tables, part_data_tables) {
+            (Some(first), Some(part)) => {
+                first.tables == part.tables && first.server_classes == part.server_classes
+            }
+            (None, part) => {
+                data_tables = part;
+                false
+            }
+            _ => false,
+        };
+
+        handler = DemoHandler::parse_all_with_analyser(NullHandler);
+        let mut packet_start = packets.pos();
+        let mut in_signon = true;
+        let mut part_tick = DemoTick::default();
+
+        while let Some(mut packet) = packets.next(&handler.state_handler)? {
+            let packet_end = packets.pos();
+            let packet_bits = stream.read_bits(packet_end - packet_start)?;
+            packet_start = packet_end;
+
+            match packet.packet_type() {
+                PacketType::Stop => break,
+                PacketType::Message | PacketType::UserCmd | PacketType::ConsoleCmd => {
+                    in_signon = false;
+                    part_tick = packet.tick();
+                    last_tick = part_tick + offset;
+                    packet.set_tick(last_tick);
+                    packet.encode(&mut out, &handler.state_handler)?;
+                    if packet.packet_type() == PacketType::Message {
+                        frames += 1;
+                    }
+                }
+                PacketType::Signon | PacketType::DataTables | PacketType::SyncTick
+                    if in_signon && skip_signon => {}
+                _ if in_signon && !skip_signon => {
+                    packet_bits.write(&mut out)?;
+                }
+                _ => {
+                    packet.set_tick(last_tick);
+                    packet.encode(&mut out, &handler.state_handler)?;
+                }
+            }
+
+            handler.handle_packet(packet)?;
+        }
+
+        interval_per_tick = handler.state_handler.demo_meta.interval_per_tick;
+        offset = offset + part_tick + 1;
+    }
+
+    let mut header = header.ok_or(ParseError::InvalidDemo("No demos to merge"))?;
+
+    Packet::Stop(StopPacket { tick: last_tick }).encode(&mut out, &handler.state_handler)?;
+
+    header.ticks = last_tick.into();
+    header.frames = frames;
+    header.duration = header.ticks as f32 * interval_per_tick;
+
+    let mut result = Vec::with_capacity(1072 + body.len());
+    header.write(&mut BitWriteStream::new(&mut result, LittleEndian))?;
+    result.extend_from_slice(&body);
+    Ok(result)
+}
+
+/// Find the data tables of a demo, they're send as part of the signon
+fn read_data_tables(mut packets: RawPacketStream) -> Result<Option<DataTablePacket>> {
+    let mut handler = DemoHandler::default();
+    while let Some(packet) = packets.next(&handler.state_handler)? {
+        match packet {
+            Packet::DataTables(tables) => return Ok(Some(tables)),
+            Packet::Message(_) => break,
+            packet => handler.handle_packet(packet)?,
+        }
+    }
+    Ok(None)
+}
@@ -140,6 +140,12 @@ impl From<GameEventTypeId> for usize {
     }
 }
 
+impl From<u16> for GameEventTypeId {
+    fn from(id: u16) -> Self {
+        GameEventTypeId(id)
+    }
+}
+
 impl From<GameEventTypeId> for u16 {
     fn from(id: GameEventTypeId) -> Self {
         id.0
@@ -1,10 +1,12 @@
 use bitbuffer::{BitReadBuffer, BitReadStream, LittleEndian};
 
+pub mod cut;
 pub mod data;
 pub mod gameevent_gen;
 pub mod gamevent;
 pub mod header;
 pub mod lzss;
+pub mod merge;
 pub mod message;
 pub mod packet;
 pub mod parser;
@@ -1,4 +1,4 @@
-use crate::demo::data::{DemoTick, ServerTick};
+use crate::demo::data::{DemoTick, ServerTick, SteamId};
 use crate::demo::gameevent_gen::{
     GameEvent, PlayerDeathEvent, PlayerSpawnEvent, TeamPlayRoundWinEvent,
 };
@@ -7,8 +7,9 @@ use crate::demo::message::usermessage::{ChatMessageKind, SayText2Message, UserMe
 use crate::demo::message::{Message, MessageType};
 use crate::demo::packet::stringtable::StringTableEntry;
 use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
+use crate::demo::parser::registry::PlayerIdentity;
 use crate::demo::vector::Vector;
-use crate::{ParserState, ReadResult, Stream};
+use crate::{ParserState, ReadResult};
 use bitbuffer::{BitWrite, BitWriteStream, Endianness};
 use num_enum::TryFromPrimitive;
 use parse_display::{Display, FromStr};
@@ -42,7 +43,19 @@ impl ChatMessage {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Copy, PartialEq, Eq, Hash, TryFromPrimitive)]
+#[derive(
+    Debug,
+    Clone,
+    Serialize,
+    Deserialize,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    TryFromPrimitive,
+)]
 #[serde(rename_all = "lowercase")]
 #[repr(u8)]
 pub enum Team {
@@ -87,7 +100,18 @@ impl Default for Team {
 }
 
 #[derive(
-    Debug, Clone, Serialize, Copy, PartialEq, Eq, Hash, TryFromPrimitive, Display, FromStr,
+    Debug,
+    Clone,
+    Serialize,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    TryFromPrimitive,
+    Display,
+    FromStr,
 )]
 #[display(style = "lowercase")]
 #[serde(rename_all = "lowercase")]
@@ -295,7 +319,7 @@ pub struct UserInfo {
     pub classes: ClassList,
     pub name: String,
     pub user_id: UserId,
-    pub steam_id: String,
+    pub steam_id: SteamId,
     pub team: Team,
     #[serde(skip)]
     pub entity_id: EntityId,
@@ -314,6 +338,19 @@ impl From<crate::demo::data::UserInfo> for UserInfo {
     }
 }
 
+impl From<&PlayerIdentity> for UserInfo {
+    fn from(player: &PlayerIdentity) -> Self {
+        UserInfo {
+            classes: ClassList::default(),
+            name: player.name.clone(),
+            user_id: player.user_id,
+            steam_id: player.steam_id.clone(),
+            entity_id: player.entity_id,
+            team: Team::default(),
+        }
+    }
+}
+
 impl PartialEq for UserInfo {
     fn eq(&self, other: &UserInfo) -> bool {
         self.classes == other.classes
@@ -324,6 +361,42 @@ impl PartialEq for UserInfo {
     }
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, Display)]
+#[display(style = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum CritType {
+    #[default]
+    None,
+    Mini,
+    Full,
+}
+
+impl CritType {
+    pub fn new(crit_type: u16) -> Self {
+        match crit_type {
+            1 => CritType::Mini,
+            2 => CritType::Full,
+            _ => CritType::None,
+        }
+    }
+}
+
+/// Bits of the `death_flags` of a player death
+pub(crate) mod death_flags {
+    pub const DOMINATION: u16 = 0x0001;
+    pub const ASSISTER_DOMINATION: u16 = 0x0002;
+    pub const REVENGE: u16 = 0x0004;
+    pub const ASSISTER_REVENGE: u16 = 0x0008;
+    pub const FIRST_BLOOD: u16 = 0x0010;
+    pub const FEIGN_DEATH: u16 = 0x0020;
+}
+
+/// Values of the `custom_kill` of a player death
+pub(crate) mod custom_kill {
+    pub const HEADSHOT: u16 = 1;
+    pub const BACKSTAB: u16 = 2;
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Death {
     pub weapon: String,
@@ -331,6 +404,13 @@ pub struct Death {
     pub assister: Option<UserId>,
     pub killer: UserId,
     pub tick: DemoTick,
+    pub weapon_def_index: u32,
+    /// The custom kill type, such as headshots and backstabs
+    pub custom_kill: u16,
+    pub crit_type: CritType,
+    pub death_flags: u16,
+    /// The number of players the shot went trough before killing the victim
+    pub penetrate_count: u16,
 }
 
 impl Death {
@@ -346,8 +426,34 @@ impl Death {
             killer: UserId::from(event.attacker),
             weapon: event.weapon.to_string(),
             victim: UserId::from(event.user_id),
+            weapon_def_index: event.weapon_def_index,
+            custom_kill: event.custom_kill,
+            crit_type: CritType::new(event.crit_type),
+            death_flags: event.death_flags,
+            penetrate_count: event.player_penetrate_count,
         }
     }
+
+    pub fn is_domination(&self) -> bool {
+        self.death_flags & death_flags::DOMINATION != 0
+    }
+
+    pub fn is_revenge(&self) -> bool {
+        self.death_flags & death_flags::REVENGE != 0
+    }
+
+    /// A spy faking their death with the dead ringer, the victim didn't actually die
+    pub fn is_feign_death(&self) -> bool {
+        self.death_flags & death_flags::FEIGN_DEATH != 0
+    }
+
+    pub fn is_headshot(&self) -> bool {
+        self.custom_kill == custom_kill::HEADSHOT
+    }
+
+    pub fn is_backstab(&self) -> bool {
+        self.custom_kill == custom_kill::BACKSTAB
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -373,10 +479,9 @@ pub struct World {
     pub boundary_max: Vector,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Analyser {
     state: MatchState,
-    user_id_map: HashMap<EntityId, UserId>,
 }
 
 impl MessageHandler for Analyser {
@@ -412,15 +517,19 @@ impl MessageHandler for Analyser {
         &mut self,
         table: &str,
         index: usize,
-        entry: &StringTableEntry,
-        _parser_state: &ParserState,
+        _entry: &StringTableEntry,
+        parser_state: &ParserState,
     ) {
         if table == "userinfo" {
-            let _ = self.parse_user_info(
-                index,
-                entry.text.as_ref().map(|s| s.as_ref()),
-                entry.extra_data.as_ref().map(|data| data.data.clone()),
-            );
+            if let Some(player) = parser_state.players.get_by_table_index(index) {
+                self.state
+                    .users
+                    .entry(player.user_id)
+                    .and_modify(|info| {
+                        info.entity_id = player.entity_id;
+                    })
+                    .or_insert_with(|| player.into());
+            }
         }
     }
 
@@ -430,7 +539,9 @@ impl MessageHandler for Analyser {
 }
 
 impl BorrowMessageHandler for Analyser {
-    fn borrow_output(&self, _state: &ParserState) -> &Self::Output {
+    type BorrowedOutput<'a> = &'a Self::Output;
+
+    fn borrow_output<'a>(&'a self, _state: &ParserState) -> Self::BorrowedOutput<'a> {
         &self.state
     }
 }
@@ -480,30 +591,9 @@ impl Analyser {
             _ => {}
         }
     }
-
-    fn parse_user_info(
-        &mut self,
-        index: usize,
-        text: Option<&str>,
-        data: Option<Stream>,
-    ) -> ReadResult<()> {
-        if let Some(user_info) =
-            crate::demo::data::UserInfo::parse_from_string_table(index as u16, text, data)?
-        {
-            self.state
-                .users
-                .entry(user_info.player_info.user_id.into())
-                .and_modify(|info| {
-                    info.entity_id = user_info.entity_id;
-                })
-                .or_insert_with(|| user_info.into());
-        }
-
-        Ok(())
-    }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct MatchState {
     pub chat: Vec<ChatMessage>,
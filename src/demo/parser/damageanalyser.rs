@@ -0,0 +1,213 @@
+use crate::demo::data::DemoTick;
+use crate::demo::gameevent_gen::{GameEvent, PlayerHurtEvent};
+use crate::demo::message::{Message, MessageType};
+use crate::demo::parser::analyser::UserId;
+use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
+use crate::ParserState;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A single instance of a player taking damage
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Hurt {
+    pub tick: DemoTick,
+    /// `None` for damage not caused by a player, such as fall damage
+    pub attacker: Option<UserId>,
+    pub victim: UserId,
+    pub weapon_id: u16,
+    pub damage: u16,
+    pub crit: bool,
+    pub mini_crit: bool,
+    /// The health of the victim after taking the damage
+    pub health: u16,
+}
+
+impl Hurt {
+    pub fn from_event(event: &PlayerHurtEvent, tick: DemoTick) -> Self {
+        Hurt {
+            tick,
+            attacker: (event.attacker != 0).then(|| UserId::from(event.attacker)),
+            victim: UserId::from(event.user_id),
+            weapon_id: event.weapon_id,
+            damage: event.damage_amount,
+            crit: event.crit,
+            mini_crit: event.mini_crit,
+            health: event.health,
+        }
+    }
+}
+
+/// Damage dealt and taken by every player
+///
+/// Damage done to yourself or without an attacking player only counts towards the damage taken,
+/// the damage dealt matrices only contain damage done by a player to another player.
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DamageState {
+    pub hurts: Vec<Hurt>,
+    /// Damage by attacker and victim
+    pub damage_by_victim: BTreeMap<UserId, BTreeMap<UserId, u32>>,
+    /// Damage by attacker and weapon id
+    pub damage_by_weapon: BTreeMap<UserId, BTreeMap<u16, u32>>,
+    pub damage_taken: BTreeMap<UserId, u32>,
+    pub interval_per_tick: f32,
+}
+
+impl DamageState {
+    /// The total damage dealt by a player to other players
+    pub fn damage_dealt(&self, attacker: UserId) -> u32 {
+        self.damage_by_victim
+            .get(&attacker)
+            .map(|victims| victims.values().sum())
+            .unwrap_or_default()
+    }
+
+    /// The damage dealt by a player per minute of the given number of ticks
+    pub fn damage_per_minute(&self, attacker: UserId, ticks: u32) -> f32 {
+        let minutes = ticks as f32 * self.interval_per_tick / 60.0;
+        if minutes > 0.0 {
+            self.damage_dealt(attacker) as f32 / minutes
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DamageAnalyser {
+    state: DamageState,
+}
+
+impl MessageHandler for DamageAnalyser {
+    type Output = DamageState;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        matches!(
+            message_type,
+            MessageType::GameEvent | MessageType::ServerInfo
+        )
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, _parser_state: &ParserState) {
+        match message {
+            Message::ServerInfo(message) => {
+                self.state.interval_per_tick = message.interval_per_tick
+            }
+            Message::GameEvent(message) => {
+                if let GameEvent::PlayerHurt(event) = &message.event {
+                    self.handle_hurt(Hurt::from_event(event, tick));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self::Output {
+        self.state
+    }
+}
+
+impl BorrowMessageHandler for DamageAnalyser {
+    type BorrowedOutput<'a> = &'a Self::Output;
+
+    fn borrow_output<'a>(&'a self, _state: &ParserState) -> Self::BorrowedOutput<'a> {
+        &self.state
+    }
+}
+
+impl DamageAnalyser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn handle_hurt(&mut self, hurt: Hurt) {
+        let damage = hurt.damage as u32;
+        *self.state.damage_taken.entry(hurt.victim).or_default() += damage;
+
+        let victim = hurt.victim;
+        if let Some(attacker) = hurt.attacker.filter(|attacker| *attacker != victim) {
+            *self
+                .state
+                .damage_by_victim
+                .entry(attacker)
+                .or_default()
+                .entry(hurt.victim)
+                .or_default() += damage;
+            *self
+                .state
+                .damage_by_weapon
+                .entry(attacker)
+                .or_default()
+                .entry(hurt.weapon_id)
+                .or_default() += damage;
+        }
+
+        self.state.hurts.push(hurt);
+    }
+}
+
+#[test]
+fn test_damage_matrices() {
+    use crate::demo::parser::test_util::*;
+
+    let hurt = |attacker: u16, victim: u16, weapon_id: u16, damage_amount: u16| {
+        event_message(GameEvent::PlayerHurt(PlayerHurtEvent {
+            user_id: victim,
+            health: 100,
+            attacker,
+            damage_amount,
+            custom: 0,
+            show_disguised_crit: false,
+            crit: false,
+            mini_crit: false,
+            all_see_crit: false,
+            weapon_id,
+            bonus_effect: 0,
+        }))
+    };
+
+    let parser_state = parser_state();
+    let mut analyser = DamageAnalyser::new();
+    let messages = [
+        (0, server_info_message("cp_process_final")),
+        (10, hurt(1, 2, 10, 50)),
+        (20, hurt(1, 3, 10, 20)),
+        (30, hurt(1, 2, 11, 30)),
+        (40, hurt(3, 1, 12, 25)),
+        // self damage from a rocket jump
+        (50, hurt(1, 1, 10, 40)),
+        // fall damage
+        (60, hurt(0, 2, 0, 15)),
+    ];
+    for (tick, message) in messages {
+        analyser.handle_message(&message, DemoTick::from(tick), &parser_state);
+    }
+    let state = analyser.into_output(&parser_state);
+
+    let user = |user_id: u16| UserId::from(user_id);
+    assert_eq!(6, state.hurts.len());
+    assert_eq!(DemoTick::from(50), state.hurts[4].tick);
+    assert_eq!(None, state.hurts[5].attacker);
+
+    assert_eq!(
+        BTreeMap::from([
+            (user(1), BTreeMap::from([(user(2), 80), (user(3), 20)])),
+            (user(3), BTreeMap::from([(user(1), 25)])),
+        ]),
+        state.damage_by_victim
+    );
+    assert_eq!(
+        BTreeMap::from([
+            (user(1), BTreeMap::from([(10, 70), (11, 30)])),
+            (user(3), BTreeMap::from([(12, 25)])),
+        ]),
+        state.damage_by_weapon
+    );
+    assert_eq!(
+        BTreeMap::from([(user(1), 65), (user(2), 95), (user(3), 20)]),
+        state.damage_taken
+    );
+    assert_eq!(100, state.damage_dealt(user(1)));
+    assert_eq!(0, state.damage_dealt(user(2)));
+    // 4000 ticks of 15ms is one minute
+    assert!((state.damage_per_minute(user(1), 4000) - 100.0).abs() < 0.001);
+}
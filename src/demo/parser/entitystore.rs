@@ -0,0 +1,217 @@
+use crate::demo::message::packetentities::{
+    EntityId, PacketEntitiesMessage, PacketEntity, UpdateType,
+};
+use crate::demo::packet::datatable::ClassId;
+use crate::demo::sendprop::{SendProp, SendPropIdentifier, SendPropValue};
+use crate::nullhasher::NullHasherBuilder;
+use crate::ParserState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The current state of an entity
+///
+/// The stored entity has the baseline of its last enter applied, so it contains the full prop set
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntityState {
+    entity: PacketEntity,
+}
+
+impl EntityState {
+    fn new(entity: &PacketEntity, parser_state: &ParserState) -> Self {
+        EntityState {
+            entity: PacketEntity {
+                server_class: entity.server_class,
+                entity_index: entity.entity_index,
+                props: entity.props(parser_state).collect(),
+                in_pvs: true,
+                update_type: UpdateType::Preserve,
+                serial_number: entity.serial_number,
+                delay: None,
+                delta: None,
+                baseline_index: entity.baseline_index,
+            },
+        }
+    }
+
+    pub fn entity_id(&self) -> EntityId {
+        self.entity.entity_index
+    }
+
+    pub fn server_class(&self) -> ClassId {
+        self.entity.server_class
+    }
+
+    pub fn serial_number(&self) -> u32 {
+        self.entity.serial_number
+    }
+
+    /// Whether the entity is in the PVS of the recording client,
+    /// entities outside the PVS keep the props from when they left the PVS
+    pub fn in_pvs(&self) -> bool {
+        self.entity.in_pvs
+    }
+
+    pub fn entity(&self) -> &PacketEntity {
+        &self.entity
+    }
+
+    pub fn get_prop(&self, identifier: SendPropIdentifier) -> Option<&SendPropValue> {
+        self.entity
+            .props
+            .iter()
+            .find(|prop| prop.identifier == identifier)
+            .map(|prop| &prop.value)
+    }
+
+    pub fn props(&self) -> impl Iterator<Item = &SendProp> {
+        self.entity.props.iter()
+    }
+}
+
+/// The full prop set of every entity, maintained by the parser when enabled with
+/// [`DemoHandler::enable_entity_store`](crate::demo::parser::DemoHandler::enable_entity_store)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntityStore {
+    entities: HashMap<EntityId, EntityState, NullHasherBuilder>,
+}
+
+impl EntityStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, entity_id: EntityId) -> Option<&EntityState> {
+        self.entities.get(&entity_id)
+    }
+
+    pub fn get_prop(
+        &self,
+        entity_id: EntityId,
+        identifier: SendPropIdentifier,
+    ) -> Option<&SendPropValue> {
+        self.get(entity_id)?.get_prop(identifier)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &EntityState> {
+        self.entities.values()
+    }
+
+    /// Get all entities of a server class
+    pub fn of_class(&self, server_class: ClassId) -> impl Iterator<Item = &EntityState> {
+        self.entities
+            .values()
+            .filter(move |entity| entity.server_class() == server_class)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Apply an entity update, this needs to happen before the parser state handles the message
+    /// so entering entities are resolved against the baselines the update was encoded against
+    pub fn handle_message(&mut self, message: &PacketEntitiesMessage, parser_state: &ParserState) {
+        // a full update contains every entity
+        if message.delta.is_none() {
+            self.entities.clear();
+        }
+
+        for removed in &message.removed_entities {
+            self.entities.remove(removed);
+        }
+
+        for entity in &message.entities {
+            match entity.update_type {
+                UpdateType::Enter => {
+                    self.entities
+                        .insert(entity.entity_index, EntityState::new(entity, parser_state));
+                }
+                UpdateType::Preserve => {
+                    if let Some(state) = self.entities.get_mut(&entity.entity_index) {
+                        state.entity.apply_update(&entity.props);
+                    }
+                }
+                UpdateType::Leave => {
+                    if let Some(state) = self.entities.get_mut(&entity.entity_index) {
+                        state.entity.apply_update(&entity.props);
+                        state.entity.in_pvs = false;
+                    }
+                }
+                UpdateType::Delete => {
+                    self.entities.remove(&entity.entity_index);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_entity_store_updates() {
+    use crate::demo::packet::datatable::{SendTable, SendTableName};
+
+    let health = SendPropIdentifier::new("DT_BasePlayer", "m_iHealth");
+    let prop = |value: i64| SendProp {
+        index: 0,
+        identifier: health,
+        value: SendPropValue::Integer(value),
+    };
+    let entity = |update_type: UpdateType, props: Vec<SendProp>| PacketEntity {
+        server_class: ClassId::from(0),
+        entity_index: EntityId::from(1u32),
+        props,
+        in_pvs: true,
+        update_type,
+        serial_number: 0,
+        delay: None,
+        delta: None,
+        baseline_index: 0,
+    };
+    let message =
+        |entities: Vec<PacketEntity>, removed_entities: Vec<EntityId>| PacketEntitiesMessage {
+            entities,
+            removed_entities,
+            max_entries: 0,
+            delta: Some(0u32.into()),
+            base_line: 0,
+            updated_base_line: false,
+        };
+
+    let mut parser_state = ParserState::new(24, |_| false, false);
+    parser_state.send_tables.push(SendTable {
+        name: SendTableName::from("DT_BasePlayer"),
+        needs_decoder: false,
+        raw_props: vec![],
+        flattened_props: vec![],
+    });
+    let mut store = EntityStore::new();
+
+    store.handle_message(
+        &message(vec![entity(UpdateType::Enter, vec![prop(125)])], vec![]),
+        &parser_state,
+    );
+    store.handle_message(
+        &message(vec![entity(UpdateType::Preserve, vec![prop(80)])], vec![]),
+        &parser_state,
+    );
+    let id = EntityId::from(1u32);
+    assert_eq!(
+        Some(&SendPropValue::Integer(80)),
+        store.get_prop(id, health)
+    );
+
+    store.handle_message(
+        &message(vec![entity(UpdateType::Leave, vec![])], vec![]),
+        &parser_state,
+    );
+    assert!(!store.get(id).unwrap().in_pvs());
+    assert_eq!(
+        Some(&SendPropValue::Integer(80)),
+        store.get_prop(id, health)
+    );
+
+    store.handle_message(&message(vec![], vec![id]), &parser_state);
+    assert!(store.is_empty());
+}
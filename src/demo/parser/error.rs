@@ -7,8 +7,6 @@ use bitbuffer::BitError;
 use err_derive::Error;
 use std::str::Utf8Error;
 use std::string::FromUtf8Error;
-use pyo3::prelude::*;
-use pyo3::exceptions::PyOSError;
 
 /// Errors that can occur during parsing
 #[derive(Debug, Error)]
@@ -83,6 +81,8 @@ pub enum ParseError {
     UnknownEntity(EntityId),
     #[error(display = "No sendprop definition found for property")]
     UnknownDefinition(SendPropIdentifier),
+    #[error(display = "Error while reading demo file: {}", _0)]
+    Io(#[error(source)] std::io::Error),
 }
 
 #[derive(Debug, Error)]
@@ -131,10 +131,4 @@ impl From<FromUtf8Error> for ParseError {
     }
 }
 
-impl std::convert::From<ParseError> for PyErr {
-    fn from(err: ParseError) -> PyErr {
-        PyOSError::new_err(err.to_string())
-    }
-}
-
 pub type Result<T> = std::result::Result<T, ParseError>;
@@ -0,0 +1,582 @@
+use crate::demo::data::DemoTick;
+use crate::demo::gameevent_gen::TeamPlayFlagEventEvent;
+use crate::demo::gamevent::GameEvent;
+use crate::demo::message::gameevent::GameEventMessage;
+use crate::demo::message::packetentities::{EntityId, PacketEntity, UpdateType};
+use crate::demo::message::{Message, MessageType};
+use crate::demo::packet::datatable::{ParseSendTable, ServerClass, ServerClassName};
+use crate::demo::parser::analyser::{Team, UserId};
+use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
+use crate::demo::parser::registry::PlayerRegistry;
+use crate::demo::sendprop::SendPropIdentifier;
+use crate::demo::vector::{Vector, VectorXY};
+use crate::ParserState;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FlagEventType {
+    Pickup,
+    Capture,
+    /// Killing the flag carrier
+    Defend,
+    Drop,
+    Return,
+}
+
+impl FlagEventType {
+    pub fn new(event_type: u16) -> Option<Self> {
+        match event_type {
+            1 => Some(FlagEventType::Pickup),
+            2 => Some(FlagEventType::Capture),
+            3 => Some(FlagEventType::Defend),
+            4 => Some(FlagEventType::Drop),
+            5 => Some(FlagEventType::Return),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FlagEvent {
+    pub tick: DemoTick,
+    pub event_type: FlagEventType,
+    /// The team owning the flag
+    pub flag_team: Team,
+    /// The player performing the action, `None` for flags returning on their own
+    pub player: Option<UserId>,
+    /// For `Defend` events, the flag carrier that was killed
+    pub carrier: Option<UserId>,
+    /// The position of the player performing the action
+    pub position: Option<Vector>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CarryOutcome {
+    Captured,
+    Dropped,
+    /// The demo ended while the flag was carried
+    #[default]
+    Unfinished,
+}
+
+/// A player carrying the flag, from picking it up until capturing or dropping it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FlagCarry {
+    pub flag_team: Team,
+    pub carrier: UserId,
+    pub start_tick: DemoTick,
+    pub end_tick: DemoTick,
+    pub outcome: CarryOutcome,
+    /// The positions of the flag during the carry
+    ///
+    /// While carried, the flag entity only networks its offset from the carrier,
+    /// so the positions are the position of the carrier with the offset of the flag added.
+    pub path: Vec<(DemoTick, Vector)>,
+    /// The position where the flag landed, for dropped flags
+    #[serde(default)]
+    pub drop_position: Option<Vector>,
+}
+
+impl FlagCarry {
+    pub fn ticks(&self) -> u32 {
+        u32::from(self.end_tick).saturating_sub(u32::from(self.start_tick))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct FlagStats {
+    pub pickups: u32,
+    pub captures: u32,
+    pub defenses: u32,
+    pub drops: u32,
+    pub returns: u32,
+    /// The number of ticks the flag was carried
+    pub carry_ticks: u32,
+}
+
+impl FlagStats {
+    fn add(&mut self, event_type: FlagEventType) {
+        match event_type {
+            FlagEventType::Pickup => self.pickups += 1,
+            FlagEventType::Capture => self.captures += 1,
+            FlagEventType::Defend => self.defenses += 1,
+            FlagEventType::Drop => self.drops += 1,
+            FlagEventType::Return => self.returns += 1,
+        }
+    }
+}
+
+/// The flag events with stats by the team and player performing them
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FlagState {
+    pub events: Vec<FlagEvent>,
+    pub carries: Vec<FlagCarry>,
+    pub teams: BTreeMap<Team, FlagStats>,
+    pub players: BTreeMap<UserId, FlagStats>,
+    /// The capture score of every team, as reported by the game
+    pub scores: BTreeMap<Team, u16>,
+    pub interval_per_tick: f32,
+}
+
+#[derive(Default, Debug, Clone)]
+struct PlayerPosition {
+    team: Team,
+    position: Vector,
+}
+
+/// `m_nFlagStatus` of a dropped flag
+const FLAG_STATUS_DROPPED: i64 = 2;
+
+#[derive(Default, Debug, Clone)]
+struct FlagEntity {
+    team: Team,
+    /// Relative to the parent while the flag is carried
+    origin: Vector,
+    parent: Option<EntityId>,
+    status: i64,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct FlagAnalyser {
+    state: FlagState,
+    tick: DemoTick,
+    players: HashMap<EntityId, PlayerPosition>,
+    flags: HashMap<EntityId, FlagEntity>,
+    /// The carry in progress for every flag, as index in `state.carries`
+    active_carries: HashMap<Team, (EntityId, usize)>,
+    /// Dropped carries for which the flag hasn't landed yet, as index in `state.carries`
+    pending_drops: HashMap<Team, usize>,
+    class_names: Vec<ServerClassName>, // indexed by ClassId
+}
+
+impl MessageHandler for FlagAnalyser {
+    type Output = FlagState;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        matches!(
+            message_type,
+            MessageType::PacketEntities | MessageType::GameEvent | MessageType::ServerInfo
+        )
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, parser_state: &ParserState) {
+        self.tick = tick;
+        match message {
+            Message::ServerInfo(message) => {
+                self.state.interval_per_tick = message.interval_per_tick
+            }
+            Message::PacketEntities(message) => {
+                for entity in &message.entities {
+                    self.handle_entity(entity, parser_state);
+                }
+            }
+            Message::GameEvent(GameEventMessage {
+                event: GameEvent::TeamPlayFlagEvent(event),
+                ..
+            }) => self.handle_flag_event(event, &parser_state.players),
+            Message::GameEvent(GameEventMessage {
+                event: GameEvent::CtfFlagCaptured(event),
+                ..
+            }) => {
+                self.state
+                    .scores
+                    .insert(Team::new(event.capping_team), event.capping_team_score);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_data_tables(
+        &mut self,
+        _parse_tables: &[ParseSendTable],
+        server_classes: &[ServerClass],
+        _parser_state: &ParserState,
+    ) {
+        self.class_names = server_classes
+            .iter()
+            .map(|class| &class.name)
+            .cloned()
+            .collect();
+    }
+
+    fn into_output(mut self, _state: &ParserState) -> Self::Output {
+        let teams: Vec<Team> = self.active_carries.keys().copied().collect();
+        for team in teams {
+            self.end_carry(team, CarryOutcome::Unfinished);
+        }
+        self.state
+    }
+}
+
+impl BorrowMessageHandler for FlagAnalyser {
+    type BorrowedOutput<'a> = &'a Self::Output;
+
+    fn borrow_output<'a>(&'a self, _state: &ParserState) -> Self::BorrowedOutput<'a> {
+        &self.state
+    }
+}
+
+impl FlagAnalyser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn handle_entity(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        let class_name: &str = self
+            .class_names
+            .get(usize::from(entity.server_class))
+            .map(|class_name| class_name.as_str())
+            .unwrap_or("");
+        match class_name {
+            "CTFPlayer" => self.handle_player_entity(entity, parser_state),
+            "CCaptureFlag" => self.handle_flag_entity(entity, parser_state),
+            _ => {}
+        }
+    }
+
+    fn handle_flag_entity(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const TEAM: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_iTeamNum");
+        const ORIGIN: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_vecOrigin");
+        const PARENT: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "moveparent");
+        const STATUS: SendPropIdentifier =
+            SendPropIdentifier::new("DT_CaptureFlag", "m_nFlagStatus");
+
+        if entity.update_type == UpdateType::Delete {
+            self.flags.remove(&entity.entity_index);
+            return;
+        }
+
+        let flag = self.flags.entry(entity.entity_index).or_default();
+        let mut moved = false;
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                TEAM => flag.team = Team::new(i64::try_from(&prop.value).unwrap_or_default()),
+                ORIGIN => {
+                    flag.origin = Vector::try_from(&prop.value).unwrap_or_default();
+                    moved = true;
+                }
+                PARENT => {
+                    // the lower 11 bits of the handle are the entity index, all set for no parent
+                    let handle = i64::try_from(&prop.value).unwrap_or_default() & 0x7FF;
+                    flag.parent = (handle != 0x7FF).then(|| EntityId::from(handle as u32));
+                    moved = true;
+                }
+                STATUS => flag.status = i64::try_from(&prop.value).unwrap_or_default(),
+                _ => {}
+            }
+        }
+
+        if moved {
+            let flag = flag.clone();
+            self.record_flag_position(&flag);
+        }
+    }
+
+    /// The position of the flag in the world, carried flags are positioned relative to their carrier
+    fn flag_position(&self, flag: &FlagEntity) -> Vector {
+        match flag.parent.and_then(|parent| self.players.get(&parent)) {
+            Some(carrier) => carrier.position + flag.origin,
+            None => flag.origin,
+        }
+    }
+
+    /// Add the position of a flag to the carry path, or set it as the landing position of a dropped flag
+    fn record_flag_position(&mut self, flag: &FlagEntity) {
+        let position = self.flag_position(flag);
+        match (flag.parent, self.active_carries.get(&flag.team)) {
+            (Some(parent), Some((carrier, index))) if parent == *carrier => {
+                self.state.carries[*index].path.push((self.tick, position));
+            }
+            (None, _) if flag.status == FLAG_STATUS_DROPPED => {
+                if let Some(index) = self.pending_drops.remove(&flag.team) {
+                    self.state.carries[index].drop_position = Some(position);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn flags_of_team(&self, team: Team) -> Vec<FlagEntity> {
+        self.flags
+            .values()
+            .filter(|flag| flag.team == team)
+            .cloned()
+            .collect()
+    }
+
+    fn handle_player_entity(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const TEAM: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_iTeamNum");
+        const LOCAL_ORIGIN: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFLocalPlayerExclusive", "m_vecOrigin");
+        const NON_LOCAL_ORIGIN: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFNonLocalPlayerExclusive", "m_vecOrigin");
+        const LOCAL_ORIGIN_Z: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFLocalPlayerExclusive", "m_vecOrigin[2]");
+        const NON_LOCAL_ORIGIN_Z: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFNonLocalPlayerExclusive", "m_vecOrigin[2]");
+
+        if entity.update_type == UpdateType::Delete {
+            self.players.remove(&entity.entity_index);
+            return;
+        }
+
+        let player = self.players.entry(entity.entity_index).or_default();
+        let mut moved = false;
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                TEAM => player.team = Team::new(i64::try_from(&prop.value).unwrap_or_default()),
+                LOCAL_ORIGIN | NON_LOCAL_ORIGIN => {
+                    let pos_xy = VectorXY::try_from(&prop.value).unwrap_or_default();
+                    player.position.x = pos_xy.x;
+                    player.position.y = pos_xy.y;
+                    moved = true;
+                }
+                LOCAL_ORIGIN_Z | NON_LOCAL_ORIGIN_Z => {
+                    player.position.z = f32::try_from(&prop.value).unwrap_or_default();
+                    moved = true;
+                }
+                _ => {}
+            }
+        }
+
+        if moved {
+            let carried: Vec<FlagEntity> = self
+                .flags
+                .values()
+                .filter(|flag| flag.parent == Some(entity.entity_index))
+                .cloned()
+                .collect();
+            for flag in carried {
+                self.record_flag_position(&flag);
+            }
+        }
+    }
+
+    fn handle_flag_event(&mut self, event: &TeamPlayFlagEventEvent, players: &PlayerRegistry) {
+        let event_type = match FlagEventType::new(event.event_type) {
+            Some(event_type) => event_type,
+            None => return,
+        };
+        let flag_team = Team::new(event.team);
+        let player_entity = EntityId::from(event.player as u32);
+        let player = players.user_id(player_entity);
+        let carrier = match event_type {
+            FlagEventType::Defend => players.user_id(EntityId::from(event.carrier as u32)),
+            _ => None,
+        };
+        let player_state = self.players.get(&player_entity);
+        let position = player_state.map(|player| player.position);
+
+        if let Some(user_id) = player {
+            self.state
+                .players
+                .entry(user_id)
+                .or_default()
+                .add(event_type);
+            if let Some(team) = player_state.map(|player| player.team) {
+                self.state.teams.entry(team).or_default().add(event_type);
+            }
+        }
+
+        match event_type {
+            FlagEventType::Pickup => {
+                self.end_carry(flag_team, CarryOutcome::Dropped);
+                if let Some(user_id) = player {
+                    self.active_carries
+                        .insert(flag_team, (player_entity, self.state.carries.len()));
+                    self.pending_drops.remove(&flag_team);
+                    self.state.carries.push(FlagCarry {
+                        flag_team,
+                        carrier: user_id,
+                        start_tick: self.tick,
+                        end_tick: self.tick,
+                        outcome: CarryOutcome::Unfinished,
+                        path: Vec::new(),
+                        drop_position: None,
+                    });
+                    // the flag entity can be updated before the event
+                    for flag in self.flags_of_team(flag_team) {
+                        self.record_flag_position(&flag);
+                    }
+                }
+            }
+            FlagEventType::Capture => self.end_carry(flag_team, CarryOutcome::Captured),
+            FlagEventType::Drop => {
+                if let Some((_, index)) = self.active_carries.get(&flag_team) {
+                    self.pending_drops.insert(flag_team, *index);
+                }
+                self.end_carry(flag_team, CarryOutcome::Dropped);
+                for flag in self.flags_of_team(flag_team) {
+                    self.record_flag_position(&flag);
+                }
+            }
+            _ => {}
+        }
+
+        self.state.events.push(FlagEvent {
+            tick: self.tick,
+            event_type,
+            flag_team,
+            player,
+            carrier,
+            position,
+        });
+    }
+
+    fn end_carry(&mut self, flag_team: Team, outcome: CarryOutcome) {
+        if let Some((entity, index)) = self.active_carries.remove(&flag_team) {
+            let carry = &mut self.state.carries[index];
+            carry.end_tick = self.tick;
+            carry.outcome = outcome;
+
+            let ticks = carry.ticks();
+            let carrier = carry.carrier;
+            self.state.players.entry(carrier).or_default().carry_ticks += ticks;
+            if let Some(player) = self.players.get(&entity) {
+                self.state.teams.entry(player.team).or_default().carry_ticks += ticks;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_flag_carries() {
+    use crate::demo::parser::test_util::*;
+    use crate::demo::sendprop::SendPropValue;
+
+    let runner = UserId::from(10u16);
+    let defender = UserId::from(20u16);
+    let mut parser_state = parser_state();
+    add_user(&mut parser_state, 0, 10);
+    add_user(&mut parser_state, 1, 20);
+
+    let server_classes = server_classes(&["CTFPlayer", "CCaptureFlag"]);
+    let entity = |class: u16, index: u32, props: Vec<(&str, &str, SendPropValue)>| {
+        entities_message(vec![packet_entity(class, index, props)])
+    };
+    let player = |index: u32, team: Team, x: f32| {
+        entity(
+            0,
+            index,
+            vec![
+                (
+                    "DT_BaseEntity",
+                    "m_iTeamNum",
+                    SendPropValue::Integer(team as i64),
+                ),
+                (
+                    "DT_TFNonLocalPlayerExclusive",
+                    "m_vecOrigin",
+                    SendPropValue::VectorXY(VectorXY { x, y: 0.0 }),
+                ),
+            ],
+        )
+    };
+    let position = |x: f32, z: f32| Vector { x, y: 0.0, z };
+    // carried flags are attached to the carrier with an offset
+    let flag = |parent: i64, origin: Vector, status: i64| {
+        entity(
+            1,
+            50,
+            vec![
+                ("DT_BaseEntity", "m_iTeamNum", SendPropValue::Integer(2)),
+                (
+                    "DT_BaseEntity",
+                    "m_vecOrigin",
+                    SendPropValue::Vector(origin),
+                ),
+                (
+                    "DT_BaseEntity",
+                    "moveparent",
+                    SendPropValue::Integer(parent),
+                ),
+                (
+                    "DT_CaptureFlag",
+                    "m_nFlagStatus",
+                    SendPropValue::Integer(status),
+                ),
+            ],
+        )
+    };
+    let no_parent = 0x7FF;
+    let flag_event = |player: u16, carrier: u16, event_type: u16| {
+        event_message(GameEvent::TeamPlayFlagEvent(TeamPlayFlagEventEvent {
+            player,
+            carrier,
+            event_type,
+            home: 0,
+            team: 2,
+        }))
+    };
+
+    let mut analyser = FlagAnalyser::new();
+    analyser.handle_data_tables(&[], &server_classes, &parser_state);
+    let mut handle = |tick: u32, messages: Vec<Message>| {
+        let tick = DemoTick::from(tick);
+        for message in messages {
+            analyser.handle_message(&message, tick, &parser_state);
+        }
+    };
+
+    handle(
+        5,
+        vec![
+            player(1, Team::Blue, 100.0),
+            player(2, Team::Red, 500.0),
+            flag(no_parent, position(1000.0, 0.0), 0),
+        ],
+    );
+    handle(
+        100,
+        vec![flag_event(1, 0, 1), flag(1, position(0.0, 10.0), 1)],
+    );
+    handle(120, vec![player(1, Team::Blue, 200.0)]);
+    handle(
+        150,
+        vec![
+            flag_event(2, 1, 3),
+            flag_event(1, 0, 4),
+            flag(no_parent, position(210.0, 0.0), 2),
+        ],
+    );
+    // the flag entity is updated before the pickup event
+    handle(
+        200,
+        vec![flag(1, position(0.0, 10.0), 1), flag_event(1, 0, 1)],
+    );
+    handle(300, vec![flag_event(1, 0, 2)]);
+    let state = analyser.into_output(&parser_state);
+
+    assert_eq!(5, state.events.len());
+    assert_eq!(Some(runner), state.events[1].carrier);
+    assert_eq!(2, state.carries.len());
+    assert_eq!(CarryOutcome::Dropped, state.carries[0].outcome);
+    assert_eq!(50, state.carries[0].ticks());
+    assert_eq!(
+        vec![
+            (DemoTick::from(100), position(100.0, 10.0)),
+            (DemoTick::from(120), position(200.0, 10.0)),
+        ],
+        state.carries[0].path
+    );
+    assert_eq!(Some(position(210.0, 0.0)), state.carries[0].drop_position);
+    assert_eq!(CarryOutcome::Captured, state.carries[1].outcome);
+    assert_eq!(
+        vec![(DemoTick::from(200), position(200.0, 10.0))],
+        state.carries[1].path
+    );
+    assert_eq!(None, state.carries[1].drop_position);
+
+    let runner_stats = &state.players[&runner];
+    assert_eq!(2, runner_stats.pickups);
+    assert_eq!(1, runner_stats.captures);
+    assert_eq!(1, runner_stats.drops);
+    assert_eq!(150, runner_stats.carry_ticks);
+    assert_eq!(1, state.players[&defender].defenses);
+    assert_eq!(150, state.teams[&Team::Blue].carry_ticks);
+    assert_eq!(1, state.teams[&Team::Red].defenses);
+}
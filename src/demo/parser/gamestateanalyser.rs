@@ -7,13 +7,13 @@ use crate::demo::message::Message;
 use crate::demo::packet::datatable::{ParseSendTable, ServerClass, ServerClassName};
 use crate::demo::packet::message::MessagePacketMeta;
 use crate::demo::packet::stringtable::StringTableEntry;
-use crate::demo::parser::analyser::UserInfo;
-pub use crate::demo::parser::analyser::{Class, Team, UserId};
+use crate::demo::parser::analyser::{custom_kill, death_flags, UserInfo};
+pub use crate::demo::parser::analyser::{Class, CritType, Team, UserId};
 use crate::demo::parser::handler::BorrowMessageHandler;
 use crate::demo::parser::MessageHandler;
 use crate::demo::sendprop::{SendProp, SendPropIdentifier, SendPropValue};
 use crate::demo::vector::{Vector, VectorXY};
-use crate::{MessageType, ParserState, ReadResult, Stream};
+use crate::{MessageType, ParserState};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::convert::TryFrom;
@@ -224,7 +224,7 @@ impl Building {
     pub fn class(&self) -> BuildingClass {
         match self {
             Building::Sentry(_) => BuildingClass::Sentry,
-            Building::Dispenser(_) => BuildingClass::Sentry,
+            Building::Dispenser(_) => BuildingClass::Dispenser,
             Building::Teleporter(_) => BuildingClass::Teleporter,
         }
     }
@@ -342,6 +342,30 @@ pub struct World {
     pub boundary_max: Vector,
 }
 
+/// The state of a player at the moment of a kill
+#[derive(Default, Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct KillSnapshot {
+    pub class: Class,
+    pub team: Team,
+    pub health: u16,
+    pub position: Vector,
+    pub view_angle: f32,
+    pub pitch_angle: f32,
+}
+
+impl From<&Player> for KillSnapshot {
+    fn from(player: &Player) -> Self {
+        KillSnapshot {
+            class: player.class,
+            team: player.team,
+            health: player.health,
+            position: player.position,
+            view_angle: player.view_angle,
+            pitch_angle: player.pitch_angle,
+        }
+    }
+}
+
 #[derive(Default, Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct Kill {
     pub attacker_id: u16,
@@ -349,6 +373,17 @@ pub struct Kill {
     pub victim_id: u16,
     pub weapon: String,
     pub tick: DemoTick,
+    pub weapon_def_index: u32,
+    /// The custom kill type, such as headshots and backstabs
+    pub custom_kill: u16,
+    pub crit_type: CritType,
+    pub death_flags: u16,
+    /// The number of players the shot went trough before killing the victim
+    pub penetrate_count: u16,
+    pub attacker: Option<KillSnapshot>,
+    pub victim: Option<KillSnapshot>,
+    /// The distance between the attacker and the victim, if both players are known
+    pub distance: Option<f32>,
 }
 
 impl Kill {
@@ -359,17 +394,116 @@ impl Kill {
             victim_id: death.user_id,
             weapon: death.weapon.to_string(),
             tick,
+            weapon_def_index: death.weapon_def_index,
+            custom_kill: death.custom_kill,
+            crit_type: CritType::new(death.crit_type),
+            death_flags: death.death_flags,
+            penetrate_count: death.player_penetrate_count,
+            attacker: None,
+            victim: None,
+            distance: None,
         }
     }
+
+    pub fn is_domination(&self) -> bool {
+        self.death_flags & death_flags::DOMINATION != 0
+    }
+
+    pub fn is_assister_domination(&self) -> bool {
+        self.death_flags & death_flags::ASSISTER_DOMINATION != 0
+    }
+
+    pub fn is_revenge(&self) -> bool {
+        self.death_flags & death_flags::REVENGE != 0
+    }
+
+    pub fn is_assister_revenge(&self) -> bool {
+        self.death_flags & death_flags::ASSISTER_REVENGE != 0
+    }
+
+    pub fn is_first_blood(&self) -> bool {
+        self.death_flags & death_flags::FIRST_BLOOD != 0
+    }
+
+    /// A spy faking their death with the dead ringer, the victim didn't actually die
+    pub fn is_feign_death(&self) -> bool {
+        self.death_flags & death_flags::FEIGN_DEATH != 0
+    }
+
+    pub fn is_headshot(&self) -> bool {
+        self.custom_kill == custom_kill::HEADSHOT
+    }
+
+    pub fn is_backstab(&self) -> bool {
+        self.custom_kill == custom_kill::BACKSTAB
+    }
+}
+
+/// Distance from the cart in which players are counted as pushing it
+///
+/// The actual capture area is defined by the map, this matches the area used by the standard payload cart.
+pub const CART_PUSH_RANGE: f32 = 150.0;
+
+/// The state of a payload cart, recorded every time the train watcher is updated
+#[derive(Default, Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct CartUpdate {
+    pub tick: DemoTick,
+    /// The team pushing the cart
+    pub team: Team,
+    pub position: Vector,
+    /// The progress along the track, from 0 to 1
+    pub progress: f32,
+    /// Negative when the cart is rolling back
+    pub speed_level: i32,
+    /// The number of players capping the cart as counted by the game, -1 when the cart is blocked
+    pub cappers: i32,
+    /// The players from the pushing team within [`CART_PUSH_RANGE`] of the cart
+    pub pushers: Vec<UserId>,
+}
+
+/// A period where the cart was blocked by the defending team
+#[derive(Default, Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct CartStall {
+    pub team: Team,
+    pub start_tick: DemoTick,
+    /// `None` if the cart is still blocked
+    pub end_tick: Option<DemoTick>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct CheckpointCapture {
+    pub tick: DemoTick,
+    pub point: u8,
+    pub point_name: String,
+    pub team: Team,
+    pub cappers: Vec<UserId>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, PartialEq)]
+/// The payload progress during a single round
+#[derive(Default, Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PayloadRound {
+    pub start_tick: DemoTick,
+    pub updates: Vec<CartUpdate>,
+    pub stalls: Vec<CartStall>,
+    pub checkpoints: Vec<CheckpointCapture>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct GameState {
     pub players: Vec<Player>,
     pub buildings: BTreeMap<EntityId, Building>,
     pub world: Option<World>,
     pub kills: Vec<Kill>,
     pub tick: DemoTick,
+    /// The cart progress of every round, only set for payload maps
+    #[serde(default)]
+    pub payload: Vec<PayloadRound>,
+}
+
+impl Player {
+    pub fn entity_id(&self) -> EntityId {
+        self.entity
+    }
 }
 
 impl GameState {
@@ -407,13 +541,54 @@ impl GameState {
     pub fn remove_building(&mut self, entity_id: EntityId) {
         self.buildings.remove(&entity_id);
     }
+
+    pub fn get_player_by_user_id(&self, user_id: UserId) -> Option<&Player> {
+        self.players.iter().find(|player| {
+            player
+                .info
+                .as_ref()
+                .map(|info| info.user_id == user_id)
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// Resolve an entity handle prop to the entity it points to
+fn entity_for_handle(value: &SendPropValue) -> EntityId {
+    // the lower 11 bits of the handle are the entity index
+    let handle = i64::try_from(value).unwrap_or_default();
+    EntityId::from((handle & 0x7FF) as u32)
 }
 
-#[derive(Default, Debug)]
+/// Resolve an entity handle prop to the player using the entity
+fn player_for_handle(value: &SendPropValue, parser_state: &ParserState) -> Option<UserId> {
+    parser_state.players.user_id(entity_for_handle(value))
+}
+
+#[derive(Default, Debug, Clone)]
+struct Train {
+    team: Team,
+    position: Vector,
+}
+
+#[derive(Default, Debug, Clone)]
+struct TrainWatcher {
+    /// The entity highlighted by the watcher, the cart prop parented to the train
+    glow_entity: Option<EntityId>,
+    last_update: CartUpdate,
+}
+
+#[derive(Default, Debug, Clone)]
 pub struct GameStateAnalyser {
     pub state: GameState,
     tick: DemoTick,
     class_names: Vec<ServerClassName>, // indexed by ClassId
+    trains: BTreeMap<EntityId, Train>,
+    train_watchers: BTreeMap<EntityId, TrainWatcher>,
+    /// The parent entity of every dynamic prop that is attached to another entity
+    prop_parents: BTreeMap<EntityId, EntityId>,
+    /// Set at the start of a round, the next payload update starts a new payload round
+    new_payload_round: bool,
 }
 
 impl MessageHandler for GameStateAnalyser {
@@ -434,14 +609,24 @@ impl MessageHandler for GameStateAnalyser {
                 }
             }
             Message::GameEvent(GameEventMessage { event, .. }) => match event {
-                GameEvent::PlayerDeath(death) => {
-                    self.state.kills.push(Kill::new(self.tick, death.as_ref()))
-                }
+                GameEvent::PlayerDeath(death) => self.handle_death(death),
                 GameEvent::RoundStart(_) => {
                     self.state.buildings.clear();
                 }
                 GameEvent::TeamPlayRoundStart(_) => {
                     self.state.buildings.clear();
+                    self.end_stalls();
+                    self.new_payload_round = true;
+                }
+                GameEvent::TeamPlayPointCaptured(event) if !self.train_watchers.is_empty() => {
+                    let capture = CheckpointCapture {
+                        tick: self.tick,
+                        point: event.cp,
+                        point_name: event.cp_name.to_string(),
+                        team: Team::new(event.team),
+                        cappers: parser_state.players.cappers(event.cappers.as_bytes()),
+                    };
+                    self.payload_round().checkpoints.push(capture);
                 }
                 GameEvent::ObjectDestroyed(ObjectDestroyedEvent { index, .. }) => {
                     self.state.remove_building((*index as u32).into());
@@ -456,15 +641,13 @@ impl MessageHandler for GameStateAnalyser {
         &mut self,
         table: &str,
         index: usize,
-        entry: &StringTableEntry,
-        _parser_state: &ParserState,
+        _entry: &StringTableEntry,
+        parser_state: &ParserState,
     ) {
         if table == "userinfo" {
-            let _ = self.parse_user_info(
-                index,
-                entry.text.as_ref().map(|s| s.as_ref()),
-                entry.extra_data.as_ref().map(|data| data.data.clone()),
-            );
+            if let Some(player) = parser_state.players.get_by_table_index(index) {
+                self.state.get_or_create_player(player.entity_id).info = Some(player.into());
+            }
         }
     }
 
@@ -497,7 +680,9 @@ impl MessageHandler for GameStateAnalyser {
 }
 
 impl BorrowMessageHandler for GameStateAnalyser {
-    fn borrow_output(&self, _state: &ParserState) -> &Self::Output {
+    type BorrowedOutput<'a> = &'a Self::Output;
+
+    fn borrow_output<'a>(&'a self, _state: &ParserState) -> Self::BorrowedOutput<'a> {
         &self.state
     }
 }
@@ -520,6 +705,9 @@ impl GameStateAnalyser {
             "CObjectSentrygun" => self.handle_sentry_entity(entity, parser_state),
             "CObjectDispenser" => self.handle_dispenser_entity(entity, parser_state),
             "CObjectTeleporter" => self.handle_teleporter_entity(entity, parser_state),
+            "CFuncTrackTrain" => self.handle_train_entity(entity, parser_state),
+            "CDynamicProp" => self.handle_dynamic_prop_entity(entity, parser_state),
+            "CTeamTrainWatcher" => self.handle_train_watcher_entity(entity, parser_state),
             _ => {}
         }
     }
@@ -674,7 +862,7 @@ impl GameStateAnalyser {
                         }
                         TARGET => {
                             sentry.auto_aim_target =
-                                UserId::from(i64::try_from(&prop.value).unwrap_or_default() as u16)
+                                player_for_handle(&prop.value, parser_state).unwrap_or_default()
                         }
                         SHELLS => {
                             sentry.shells = i64::try_from(&prop.value).unwrap_or_default() as u16
@@ -869,7 +1057,7 @@ impl GameStateAnalyser {
                         LEVEL => *level = i64::try_from(&prop.value).unwrap_or_default() as u8,
                         BUILDER => {
                             *builder =
-                                UserId::from(i64::try_from(&prop.value).unwrap_or_default() as u16)
+                                player_for_handle(&prop.value, parser_state).unwrap_or_default()
                         }
                         MAX_HEALTH => {
                             *max_health = i64::try_from(&prop.value).unwrap_or_default() as u16
@@ -882,19 +1070,394 @@ impl GameStateAnalyser {
         }
     }
 
-    fn parse_user_info(
+    fn handle_death(&mut self, death: &PlayerDeathEvent) {
+        let mut kill = Kill::new(self.tick, death);
+        kill.attacker = self
+            .state
+            .get_player_by_user_id(UserId::from(death.attacker))
+            .map(KillSnapshot::from);
+        kill.victim = self
+            .state
+            .get_player_by_user_id(UserId::from(death.user_id))
+            .map(KillSnapshot::from);
+        if let (Some(attacker), Some(victim)) = (&kill.attacker, &kill.victim) {
+            kill.distance = Some((attacker.position - victim.position).length());
+        }
+        self.state.kills.push(kill)
+    }
+
+    pub fn handle_train_entity(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const ORIGIN: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_vecOrigin");
+        const TEAM: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_iTeamNum");
+
+        if entity.update_type == UpdateType::Delete {
+            self.trains.remove(&entity.entity_index);
+            return;
+        }
+
+        let train = self.trains.entry(entity.entity_index).or_default();
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                ORIGIN => train.position = Vector::try_from(&prop.value).unwrap_or_default(),
+                TEAM => train.team = Team::new(i64::try_from(&prop.value).unwrap_or_default()),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn handle_dynamic_prop_entity(
         &mut self,
-        index: usize,
-        text: Option<&str>,
-        data: Option<Stream>,
-    ) -> ReadResult<()> {
-        if let Some(user_info) =
-            crate::demo::data::UserInfo::parse_from_string_table(index as u16, text, data)?
-        {
-            let id = user_info.entity_id;
-            self.state.get_or_create_player(id).info = Some(user_info.into());
+        entity: &PacketEntity,
+        parser_state: &ParserState,
+    ) {
+        const PARENT: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "moveparent");
+
+        if entity.update_type == UpdateType::Delete {
+            self.prop_parents.remove(&entity.entity_index);
+            return;
+        }
+
+        for prop in entity.props(parser_state) {
+            if prop.identifier == PARENT {
+                self.prop_parents
+                    .insert(entity.entity_index, entity_for_handle(&prop.value));
+            }
+        }
+    }
+
+    pub fn handle_train_watcher_entity(
+        &mut self,
+        entity: &PacketEntity,
+        parser_state: &ParserState,
+    ) {
+        const TEAM: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_iTeamNum");
+        const PROGRESS: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamTrainWatcher", "m_flTotalProgress");
+        const SPEED_LEVEL: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamTrainWatcher", "m_iTrainSpeedLevel");
+        const CAPPERS: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamTrainWatcher", "m_nNumCappers");
+        const GLOW_ENTITY: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TeamTrainWatcher", "m_hGlowEnt");
+
+        if entity.update_type == UpdateType::Delete {
+            self.train_watchers.remove(&entity.entity_index);
+            return;
         }
 
-        Ok(())
+        let TrainWatcher {
+            mut glow_entity,
+            last_update: mut update,
+        } = self
+            .train_watchers
+            .get(&entity.entity_index)
+            .cloned()
+            .unwrap_or_default();
+        let was_blocked = update.cappers < 0;
+
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                GLOW_ENTITY => glow_entity = Some(entity_for_handle(&prop.value)),
+                TEAM => update.team = Team::new(i64::try_from(&prop.value).unwrap_or_default()),
+                PROGRESS => update.progress = f32::try_from(&prop.value).unwrap_or_default(),
+                SPEED_LEVEL => {
+                    update.speed_level = i64::try_from(&prop.value).unwrap_or_default() as i32
+                }
+                CAPPERS => update.cappers = i64::try_from(&prop.value).unwrap_or_default() as i32,
+                _ => {}
+            }
+        }
+
+        // the watcher highlights the cart prop attached to the train it's watching
+        if let Some(train) = glow_entity.and_then(|glow_entity| {
+            self.trains.get(&glow_entity).or_else(|| {
+                self.prop_parents
+                    .get(&glow_entity)
+                    .and_then(|parent| self.trains.get(parent))
+            })
+        }) {
+            update.position = train.position;
+        }
+        update.tick = self.tick;
+        update.pushers = self
+            .state
+            .players
+            .iter()
+            .filter(|player| {
+                player.team == update.team
+                    && player.state == PlayerState::Alive
+                    && (player.position - update.position).length() <= CART_PUSH_RANGE
+            })
+            .filter_map(|player| player.info.as_ref().map(|info| info.user_id))
+            .collect();
+
+        let tick = self.tick;
+        let team = update.team;
+        let is_blocked = update.cappers < 0;
+        self.train_watchers.insert(
+            entity.entity_index,
+            TrainWatcher {
+                glow_entity,
+                last_update: update.clone(),
+            },
+        );
+
+        let round = self.payload_round();
+        round.updates.push(update);
+        if is_blocked && !was_blocked {
+            round.stalls.push(CartStall {
+                team,
+                start_tick: tick,
+                end_tick: None,
+            });
+        } else if was_blocked && !is_blocked {
+            if let Some(stall) = round
+                .stalls
+                .iter_mut()
+                .rev()
+                .find(|stall| stall.team == team && stall.end_tick.is_none())
+            {
+                stall.end_tick = Some(tick);
+            }
+        }
+    }
+
+    fn payload_round(&mut self) -> &mut PayloadRound {
+        if self.new_payload_round || self.state.payload.is_empty() {
+            self.new_payload_round = false;
+            self.state.payload.push(PayloadRound {
+                start_tick: self.tick,
+                ..PayloadRound::default()
+            });
+        }
+        self.state.payload.last_mut().unwrap()
+    }
+
+    fn end_stalls(&mut self) {
+        let tick = self.tick;
+        if let Some(round) = self.state.payload.last_mut() {
+            for stall in round.stalls.iter_mut() {
+                stall.end_tick.get_or_insert(tick);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_payload_timeline() {
+    use crate::demo::gameevent_gen::TeamPlayPointCapturedEvent;
+    use crate::demo::parser::test_util::*;
+
+    let classes = [
+        "CTFPlayer",
+        "CTFPlayerResource",
+        "CFuncTrackTrain",
+        "CDynamicProp",
+        "CTeamTrainWatcher",
+    ];
+    let entity = |class: &str, index: u32, props: Vec<(&str, &str, SendPropValue)>| {
+        let class = classes.iter().position(|name| *name == class).unwrap();
+        packet_entity(class as u16, index, props)
+    };
+    let position = |x: f32| Vector { x, y: 0.0, z: 0.0 };
+    let watcher = |progress: f32, cappers: i64| {
+        entity(
+            "CTeamTrainWatcher",
+            100,
+            vec![
+                ("DT_BaseEntity", "m_iTeamNum", SendPropValue::Integer(3)),
+                // a handle to entity 103 with serial number 1
+                (
+                    "DT_TeamTrainWatcher",
+                    "m_hGlowEnt",
+                    SendPropValue::Integer(103 | 1 << 11),
+                ),
+                (
+                    "DT_TeamTrainWatcher",
+                    "m_flTotalProgress",
+                    SendPropValue::Float(progress),
+                ),
+                (
+                    "DT_TeamTrainWatcher",
+                    "m_nNumCappers",
+                    SendPropValue::Integer(cappers),
+                ),
+            ],
+        )
+    };
+
+    let mut parser_state = parser_state();
+    let user_info = add_user(&mut parser_state, 0, 5);
+
+    let mut analyser = GameStateAnalyser::new();
+    analyser.handle_data_tables(&[], &server_classes(&classes), &parser_state);
+    analyser.handle_string_entry("userinfo", 0, &user_info, &parser_state);
+
+    let mut handle = |tick: u32, message: Message| {
+        let tick = DemoTick::from(tick);
+        analyser.handle_packet_meta(tick, &MessagePacketMeta::default(), &parser_state);
+        analyser.handle_message(&message, tick, &parser_state);
+    };
+    let entities = entities_message;
+
+    handle(
+        5,
+        entities(vec![
+            entity(
+                "CTFPlayer",
+                1,
+                vec![(
+                    "DT_TFNonLocalPlayerExclusive",
+                    "m_vecOrigin",
+                    SendPropValue::VectorXY(VectorXY { x: 150.0, y: 0.0 }),
+                )],
+            ),
+            entity(
+                "CTFPlayerResource",
+                2,
+                vec![("m_iTeam", "001", SendPropValue::Integer(3))],
+            ),
+            // a blue train that isn't watched by the watcher
+            entity(
+                "CFuncTrackTrain",
+                101,
+                vec![
+                    ("DT_BaseEntity", "m_iTeamNum", SendPropValue::Integer(3)),
+                    (
+                        "DT_BaseEntity",
+                        "m_vecOrigin",
+                        SendPropValue::Vector(position(-1000.0)),
+                    ),
+                ],
+            ),
+            entity(
+                "CFuncTrackTrain",
+                102,
+                vec![(
+                    "DT_BaseEntity",
+                    "m_vecOrigin",
+                    SendPropValue::Vector(position(100.0)),
+                )],
+            ),
+            // the cart prop attached to the watched train
+            entity(
+                "CDynamicProp",
+                103,
+                vec![(
+                    "DT_BaseEntity",
+                    "moveparent",
+                    SendPropValue::Integer(102 | 1 << 11),
+                )],
+            ),
+        ]),
+    );
+    handle(10, entities(vec![watcher(0.01, 1)]));
+    handle(20, entities(vec![watcher(0.02, -1)]));
+    handle(50, entities(vec![watcher(0.05, 1)]));
+    handle(
+        60,
+        event_message(GameEvent::TeamPlayPointCaptured(
+            TeamPlayPointCapturedEvent {
+                cp: 0,
+                cp_name: "Checkpoint".into(),
+                team: 3,
+                cappers: "\u{1}".into(),
+            },
+        )),
+    );
+    let state = analyser.into_output(&parser_state);
+
+    assert_eq!(1, state.payload.len());
+    let round = &state.payload[0];
+    assert_eq!(3, round.updates.len());
+    assert_eq!(Team::Blue, round.updates[0].team);
+    assert_eq!(position(100.0), round.updates[0].position);
+    assert_eq!(vec![UserId::from(5u16)], round.updates[0].pushers);
+    assert!((round.updates[2].progress - 0.05).abs() < 0.001);
+    assert_eq!(
+        vec![CartStall {
+            team: Team::Blue,
+            start_tick: DemoTick::from(20),
+            end_tick: Some(DemoTick::from(50)),
+        }],
+        round.stalls
+    );
+    assert_eq!(1, round.checkpoints.len());
+    assert_eq!(vec![UserId::from(5u16)], round.checkpoints[0].cappers);
+}
+
+#[test]
+fn test_kill_snapshots() {
+    use crate::demo::parser::test_util::*;
+
+    let mut parser_state = parser_state();
+    let mut analyser = GameStateAnalyser::new();
+    analyser.handle_data_tables(
+        &[],
+        &server_classes(&["CTFPlayer", "CTFPlayerResource"]),
+        &parser_state,
+    );
+
+    let mut entities = Vec::new();
+    // the sniper (user 10) kills the scout (user 20)
+    for (index, (user_id, class, x)) in [(10u16, 2i64, 0.0), (20, 1, 300.0)].into_iter().enumerate()
+    {
+        let user_info = add_user(&mut parser_state, index, user_id);
+        analyser.handle_string_entry("userinfo", index, &user_info, &parser_state);
+
+        let slot = format!("{:03}", index + 1);
+        entities.push(packet_entity(
+            0,
+            index as u32 + 1,
+            vec![
+                ("DT_BasePlayer", "m_iHealth", SendPropValue::Integer(125)),
+                (
+                    "DT_TFNonLocalPlayerExclusive",
+                    "m_vecOrigin",
+                    SendPropValue::VectorXY(VectorXY { x, y: 400.0 }),
+                ),
+            ],
+        ));
+        entities.push(packet_entity(
+            1,
+            30,
+            vec![
+                ("m_iTeam", &slot, SendPropValue::Integer(2)),
+                ("m_iPlayerClass", &slot, SendPropValue::Integer(class)),
+            ],
+        ));
+    }
+
+    let tick = DemoTick::from(10);
+    let messages = [
+        entities_message(entities),
+        event_message(GameEvent::PlayerDeath(Box::new(PlayerDeathEvent {
+            victim_ent_index: 2,
+            inflictor_ent_index: 1,
+            custom_kill: 1,
+            death_flags: 0x0001 | 0x0010,
+            weapon_def_index: 14,
+            crit_type: 2,
+            ..death_event(20, 10, "sniperrifle")
+        }))),
+    ];
+    for message in &messages {
+        analyser.handle_packet_meta(tick, &MessagePacketMeta::default(), &parser_state);
+        analyser.handle_message(message, tick, &parser_state);
     }
+    let state = analyser.into_output(&parser_state);
+
+    let kill = &state.kills[0];
+    assert_eq!(tick, kill.tick);
+    assert!(kill.is_headshot());
+    assert!(kill.is_domination());
+    assert!(kill.is_first_blood());
+    assert!(!kill.is_feign_death());
+    assert_eq!(CritType::Full, kill.crit_type);
+    let attacker = kill.attacker.as_ref().unwrap();
+    assert_eq!(Class::Sniper, attacker.class);
+    assert_eq!(Team::Red, attacker.team);
+    assert_eq!(125, attacker.health);
+    assert_eq!(Class::Scout, kill.victim.as_ref().unwrap().class);
+    assert_eq!(Some(300.0), kill.distance);
 }
@@ -1,5 +1,8 @@
+use crate::demo::message::packetentities::{
+    EntityId, PacketEntitiesMessage, PacketEntity, UpdateType,
+};
 use crate::demo::message::{Message, MessageType};
-use crate::demo::packet::datatable::{ParseSendTable, ServerClass};
+use crate::demo::packet::datatable::{ClassId, ParseSendTable, ServerClass, ServerClassName};
 use crate::demo::packet::stringtable::{StringTable, StringTableEntry};
 use crate::demo::packet::Packet;
 use crate::Result;
@@ -8,6 +11,7 @@ use crate::demo::data::{DemoTick, ServerTick};
 use crate::demo::header::Header;
 use crate::demo::packet::message::MessagePacketMeta;
 use crate::ParserState;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 
 pub trait MessageHandler {
@@ -45,11 +49,314 @@ pub trait MessageHandler {
     ) {
     }
 
+    /// Called when an entity enters the PVS, `entity.props` only contains the props that differ
+    /// from the baseline, use [`PacketEntity::props`] to get the full prop set
+    ///
+    /// Entity hooks are only called for handlers that handle [`MessageType::PacketEntities`]
+    fn handle_entity_enter(
+        &mut self,
+        _entity: &PacketEntity,
+        _class_name: &ServerClassName,
+        _tick: DemoTick,
+        _parser_state: &ParserState,
+    ) {
+    }
+
+    /// Called when an entity in the PVS is updated, `entity.props` only contains the changed props
+    fn handle_entity_update(
+        &mut self,
+        _entity: &PacketEntity,
+        _class_name: &ServerClassName,
+        _tick: DemoTick,
+        _parser_state: &ParserState,
+    ) {
+    }
+
+    /// Called when an entity leaves the PVS, the entity isn't deleted and can enter the PVS again
+    fn handle_entity_leave(
+        &mut self,
+        _entity: &PacketEntity,
+        _class_name: &ServerClassName,
+        _tick: DemoTick,
+        _parser_state: &ParserState,
+    ) {
+    }
+
+    /// Called when an entity is deleted, the class name is `None` for entities that were
+    /// never seen, e.g. entities deleted in the first packets of a demo that was cut
+    fn handle_entity_delete(
+        &mut self,
+        _entity_id: EntityId,
+        _class_name: Option<&ServerClassName>,
+        _tick: DemoTick,
+        _parser_state: &ParserState,
+    ) {
+    }
+
+    /// Called once all packets of a tick are handled
+    fn handle_tick_end(&mut self, _tick: DemoTick, _parser_state: &ParserState) {}
+
     fn into_output(self, state: &ParserState) -> Self::Output;
 }
 
 pub trait BorrowMessageHandler: MessageHandler {
-    fn borrow_output(&self, _state: &ParserState) -> &Self::Output;
+    /// A borrowed view of the output, for most handlers this is simply `&'a Self::Output`
+    type BorrowedOutput<'a>
+    where
+        Self: 'a;
+
+    fn borrow_output<'a>(&'a self, state: &ParserState) -> Self::BorrowedOutput<'a>;
+}
+
+/// Run multiple handlers in a single pass over the demo by combining them in a tuple
+///
+/// The combined handler handles every message type that any of the handlers handles,
+/// messages are only forwarded to the handlers that requested them.
+macro_rules! impl_tuple_handler {
+    ($($handler:ident: $index:tt),+) => {
+        impl<$($handler: MessageHandler),+> MessageHandler for ($($handler,)+) {
+            type Output = ($($handler::Output,)+);
+
+            fn does_handle(message_type: MessageType) -> bool {
+                $($handler::does_handle(message_type))||+
+            }
+
+            fn handle_header(&mut self, header: &Header) {
+                $(self.$index.handle_header(header);)+
+            }
+
+            fn handle_message(&mut self, message: &Message, tick: DemoTick, parser_state: &ParserState) {
+                let message_type = message.get_message_type();
+                $(
+                    if $handler::does_handle(message_type) {
+                        self.$index.handle_message(message, tick, parser_state);
+                    }
+                )+
+            }
+
+            fn handle_string_entry(
+                &mut self,
+                table: &str,
+                index: usize,
+                entries: &StringTableEntry,
+                parser_state: &ParserState,
+            ) {
+                $(self.$index.handle_string_entry(table, index, entries, parser_state);)+
+            }
+
+            fn handle_data_tables(
+                &mut self,
+                tables: &[ParseSendTable],
+                server_classes: &[ServerClass],
+                parser_state: &ParserState,
+            ) {
+                $(self.$index.handle_data_tables(tables, server_classes, parser_state);)+
+            }
+
+            fn handle_packet_meta(
+                &mut self,
+                tick: DemoTick,
+                meta: &MessagePacketMeta,
+                parser_state: &ParserState,
+            ) {
+                $(self.$index.handle_packet_meta(tick, meta, parser_state);)+
+            }
+
+            fn handle_entity_enter(
+                &mut self,
+                entity: &PacketEntity,
+                class_name: &ServerClassName,
+                tick: DemoTick,
+                parser_state: &ParserState,
+            ) {
+                $(
+                    if $handler::does_handle(MessageType::PacketEntities) {
+                        self.$index.handle_entity_enter(entity, class_name, tick, parser_state);
+                    }
+                )+
+            }
+
+            fn handle_entity_update(
+                &mut self,
+                entity: &PacketEntity,
+                class_name: &ServerClassName,
+                tick: DemoTick,
+                parser_state: &ParserState,
+            ) {
+                $(
+                    if $handler::does_handle(MessageType::PacketEntities) {
+                        self.$index.handle_entity_update(entity, class_name, tick, parser_state);
+                    }
+                )+
+            }
+
+            fn handle_entity_leave(
+                &mut self,
+                entity: &PacketEntity,
+                class_name: &ServerClassName,
+                tick: DemoTick,
+                parser_state: &ParserState,
+            ) {
+                $(
+                    if $handler::does_handle(MessageType::PacketEntities) {
+                        self.$index.handle_entity_leave(entity, class_name, tick, parser_state);
+                    }
+                )+
+            }
+
+            fn handle_entity_delete(
+                &mut self,
+                entity_id: EntityId,
+                class_name: Option<&ServerClassName>,
+                tick: DemoTick,
+                parser_state: &ParserState,
+            ) {
+                $(
+                    if $handler::does_handle(MessageType::PacketEntities) {
+                        self.$index.handle_entity_delete(entity_id, class_name, tick, parser_state);
+                    }
+                )+
+            }
+
+            fn handle_tick_end(&mut self, tick: DemoTick, parser_state: &ParserState) {
+                $(self.$index.handle_tick_end(tick, parser_state);)+
+            }
+
+            fn into_output(self, state: &ParserState) -> Self::Output {
+                ($(self.$index.into_output(state),)+)
+            }
+        }
+
+        impl<$($handler: BorrowMessageHandler),+> BorrowMessageHandler for ($($handler,)+) {
+            type BorrowedOutput<'a> = ($($handler::BorrowedOutput<'a>,)+) where Self: 'a;
+
+            fn borrow_output<'a>(&'a self, state: &ParserState) -> Self::BorrowedOutput<'a> {
+                ($(self.$index.borrow_output(state),)+)
+            }
+        }
+    };
+}
+
+impl_tuple_handler!(A: 0, B: 1);
+impl_tuple_handler!(A: 0, B: 1, C: 2);
+impl_tuple_handler!(A: 0, B: 1, C: 2, D: 3);
+impl_tuple_handler!(A: 0, B: 1, C: 2, D: 3, E: 4);
+impl_tuple_handler!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+
+/// An optional handler, allows enabling or disabling a handler at runtime
+///
+/// Since `does_handle` can't see the handler itself, the parser will still parse the messages
+/// requested by the handler even if it's disabled.
+impl<T: MessageHandler> MessageHandler for Option<T> {
+    type Output = Option<T::Output>;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        T::does_handle(message_type)
+    }
+
+    fn handle_header(&mut self, header: &Header) {
+        if let Some(handler) = self {
+            handler.handle_header(header);
+        }
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, parser_state: &ParserState) {
+        if let Some(handler) = self {
+            handler.handle_message(message, tick, parser_state);
+        }
+    }
+
+    fn handle_string_entry(
+        &mut self,
+        table: &str,
+        index: usize,
+        entries: &StringTableEntry,
+        parser_state: &ParserState,
+    ) {
+        if let Some(handler) = self {
+            handler.handle_string_entry(table, index, entries, parser_state);
+        }
+    }
+
+    fn handle_data_tables(
+        &mut self,
+        tables: &[ParseSendTable],
+        server_classes: &[ServerClass],
+        parser_state: &ParserState,
+    ) {
+        if let Some(handler) = self {
+            handler.handle_data_tables(tables, server_classes, parser_state);
+        }
+    }
+
+    fn handle_packet_meta(
+        &mut self,
+        tick: DemoTick,
+        meta: &MessagePacketMeta,
+        parser_state: &ParserState,
+    ) {
+        if let Some(handler) = self {
+            handler.handle_packet_meta(tick, meta, parser_state);
+        }
+    }
+
+    fn handle_entity_enter(
+        &mut self,
+        entity: &PacketEntity,
+        class_name: &ServerClassName,
+        tick: DemoTick,
+        parser_state: &ParserState,
+    ) {
+        if let Some(handler) = self {
+            handler.handle_entity_enter(entity, class_name, tick, parser_state);
+        }
+    }
+
+    fn handle_entity_update(
+        &mut self,
+        entity: &PacketEntity,
+        class_name: &ServerClassName,
+        tick: DemoTick,
+        parser_state: &ParserState,
+    ) {
+        if let Some(handler) = self {
+            handler.handle_entity_update(entity, class_name, tick, parser_state);
+        }
+    }
+
+    fn handle_entity_leave(
+        &mut self,
+        entity: &PacketEntity,
+        class_name: &ServerClassName,
+        tick: DemoTick,
+        parser_state: &ParserState,
+    ) {
+        if let Some(handler) = self {
+            handler.handle_entity_leave(entity, class_name, tick, parser_state);
+        }
+    }
+
+    fn handle_entity_delete(
+        &mut self,
+        entity_id: EntityId,
+        class_name: Option<&ServerClassName>,
+        tick: DemoTick,
+        parser_state: &ParserState,
+    ) {
+        if let Some(handler) = self {
+            handler.handle_entity_delete(entity_id, class_name, tick, parser_state);
+        }
+    }
+
+    fn handle_tick_end(&mut self, tick: DemoTick, parser_state: &ParserState) {
+        if let Some(handler) = self {
+            handler.handle_tick_end(tick, parser_state);
+        }
+    }
+
+    fn into_output(self, state: &ParserState) -> Self::Output {
+        self.map(|handler| handler.into_output(state))
+    }
 }
 
 pub struct NullHandler;
@@ -64,6 +371,21 @@ impl MessageHandler for NullHandler {
     fn into_output(self, _state: &ParserState) -> Self::Output {}
 }
 
+/// A checkpoint of the parser at a packet boundary, from which parsing can be resumed
+///
+/// Only the parser state is stored, analysers resuming from a checkpoint start out empty.
+/// The data tables are passed to the analyser again when resuming,
+/// string table entries from before the checkpoint are not.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ParserCheckpoint {
+    pub state: ParserState,
+    pub string_table_names: Vec<String>,
+    pub server_tick: ServerTick,
+    pub demo_tick: DemoTick,
+    /// The position in bits of the next packet in the demo
+    pub position: usize,
+}
+
 #[derive(Clone)]
 pub struct DemoHandler<'a, T: MessageHandler> {
     pub server_tick: ServerTick,
@@ -109,12 +431,60 @@ impl<'a, T: MessageHandler> DemoHandler<'a, T> {
         }
     }
 
+    pub fn checkpoint(&self, position: usize) -> ParserCheckpoint {
+        ParserCheckpoint {
+            state: self.state_handler.clone(),
+            string_table_names: self
+                .string_table_names
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            server_tick: self.server_tick,
+            demo_tick: self.demo_tick,
+            position,
+        }
+    }
+
+    pub fn restore_checkpoint(&mut self, checkpoint: ParserCheckpoint) {
+        let mut state = checkpoint.state;
+        state.set_analyser_handles(T::does_handle);
+        self.state_handler = state;
+        self.string_table_names = checkpoint
+            .string_table_names
+            .into_iter()
+            .map(Cow::Owned)
+            .collect();
+        self.server_tick = checkpoint.server_tick;
+        self.demo_tick = checkpoint.demo_tick;
+
+        let send_tables: Vec<ParseSendTable> = self
+            .state_handler
+            .send_tables
+            .iter()
+            .map(|table| ParseSendTable {
+                name: table.name.clone(),
+                props: table.raw_props.clone(),
+                needs_decoder: table.needs_decoder,
+            })
+            .collect();
+        self.analyser.handle_data_tables(
+            &send_tables,
+            &self.state_handler.server_classes,
+            &self.state_handler,
+        );
+    }
+
     pub fn handle_header(&mut self, header: &Header) {
         self.state_handler.protocol_version = header.protocol;
         self.analyser.handle_header(header);
     }
 
     pub fn handle_packet(&mut self, packet: Packet<'a>) -> Result<()> {
+        if packet.tick() != self.demo_tick {
+            self.analyser
+                .handle_tick_end(self.demo_tick, &self.state_handler);
+        }
+        self.demo_tick = packet.tick();
         match packet {
             Packet::DataTables(packet) => {
                 self.handle_data_table(packet.tables, packet.server_classes)?;
@@ -192,16 +562,79 @@ impl<'a, T: MessageHandler> DemoHandler<'a, T> {
             .handle_data_table(send_tables, server_classes)
     }
 
+    /// Keep track of the props of all entities, the entity store is available to the analyser through the [`ParserState`]
+    pub fn enable_entity_store(&mut self) {
+        self.state_handler.enable_entity_store();
+    }
+
     pub fn handle_message(&mut self, message: Message<'a>, tick: DemoTick) {
         let message_type = message.get_message_type();
+        if let Message::PacketEntities(message) = &message {
+            self.state_handler.update_entity_store(message);
+        }
         if T::does_handle(message_type) {
             self.analyser
                 .handle_message(&message, tick, &self.state_handler);
+            if let Message::PacketEntities(message) = &message {
+                self.handle_entities(message, tick);
+            }
         }
         self.state_handler.handle_message(message, tick);
     }
 
-    pub fn into_output(self) -> T::Output {
+    /// Call the entity hooks of the analyser, this needs to happen before the parser state handles the message
+    /// so the classes of leaving and deleted entities can still be resolved
+    fn handle_entities(&mut self, message: &PacketEntitiesMessage, tick: DemoTick) {
+        let state = &self.state_handler;
+        let class_name = |class: ClassId| {
+            state
+                .server_classes
+                .get(usize::from(class))
+                .map(|class| &class.name)
+        };
+        let known_class_name = |entity_id: &EntityId| {
+            state
+                .entity_classes
+                .get(entity_id)
+                .and_then(|class| class_name(*class))
+        };
+
+        for removed in &message.removed_entities {
+            self.analyser
+                .handle_entity_delete(*removed, known_class_name(removed), tick, state);
+        }
+
+        for entity in &message.entities {
+            match entity.update_type {
+                UpdateType::Enter => {
+                    if let Some(name) = class_name(entity.server_class) {
+                        self.analyser.handle_entity_enter(entity, name, tick, state);
+                    }
+                }
+                UpdateType::Preserve => {
+                    if let Some(name) = class_name(entity.server_class) {
+                        self.analyser
+                            .handle_entity_update(entity, name, tick, state);
+                    }
+                }
+                UpdateType::Leave => {
+                    if let Some(name) = known_class_name(&entity.entity_index) {
+                        self.analyser.handle_entity_leave(entity, name, tick, state);
+                    }
+                }
+                UpdateType::Delete => {
+                    let name = known_class_name(&entity.entity_index);
+                    self.analyser
+                        .handle_entity_delete(entity.entity_index, name, tick, state);
+                }
+            }
+        }
+    }
+
+    /// Get the output of the analyser, ending the last tick
+    pub fn into_output(mut self) -> T::Output {
+        self.analyser
+            .handle_tick_end(self.demo_tick, &self.state_handler);
         self.analyser.into_output(&self.state_handler)
     }
 
@@ -211,7 +644,143 @@ impl<'a, T: MessageHandler> DemoHandler<'a, T> {
 }
 
 impl<T: MessageHandler + BorrowMessageHandler> DemoHandler<'_, T> {
-    pub fn borrow_output(&self) -> &T::Output {
+    pub fn borrow_output(&self) -> T::BorrowedOutput<'_> {
         self.analyser.borrow_output(&self.state_handler)
     }
 }
+
+#[test]
+fn test_entity_hooks() {
+    use crate::demo::packet::message::MessagePacket;
+    use crate::demo::parser::test_util::packet_entity;
+
+    struct EntityEvents(Vec<String>);
+
+    impl MessageHandler for EntityEvents {
+        type Output = Vec<String>;
+
+        fn does_handle(message_type: MessageType) -> bool {
+            matches!(message_type, MessageType::PacketEntities)
+        }
+
+        fn handle_entity_enter(
+            &mut self,
+            entity: &PacketEntity,
+            class_name: &ServerClassName,
+            _tick: DemoTick,
+            _parser_state: &ParserState,
+        ) {
+            self.0
+                .push(format!("enter {} {}", entity.entity_index, class_name));
+        }
+
+        fn handle_entity_update(
+            &mut self,
+            entity: &PacketEntity,
+            _class_name: &ServerClassName,
+            _tick: DemoTick,
+            _parser_state: &ParserState,
+        ) {
+            self.0.push(format!(
+                "update {} {}",
+                entity.entity_index,
+                entity.props.len()
+            ));
+        }
+
+        fn handle_entity_leave(
+            &mut self,
+            entity: &PacketEntity,
+            class_name: &ServerClassName,
+            _tick: DemoTick,
+            _parser_state: &ParserState,
+        ) {
+            self.0
+                .push(format!("leave {} {}", entity.entity_index, class_name));
+        }
+
+        fn handle_entity_delete(
+            &mut self,
+            entity_id: EntityId,
+            class_name: Option<&ServerClassName>,
+            _tick: DemoTick,
+            _parser_state: &ParserState,
+        ) {
+            let class_name = class_name.map(|name| name.as_str()).unwrap_or("unknown");
+            self.0.push(format!("delete {} {}", entity_id, class_name));
+        }
+
+        fn handle_tick_end(&mut self, tick: DemoTick, _parser_state: &ParserState) {
+            self.0.push(format!("end {}", u32::from(tick)));
+        }
+
+        fn into_output(self, _state: &ParserState) -> Self::Output {
+            self.0
+        }
+    }
+
+    let entity = |entity_index: u32, update_type: UpdateType| PacketEntity {
+        update_type,
+        ..packet_entity(0, entity_index, vec![])
+    };
+    // a delta update, so the entities that aren't in the message are kept
+    let message = |entities: Vec<PacketEntity>, removed_entities: Vec<EntityId>| {
+        Message::PacketEntities(PacketEntitiesMessage {
+            entities,
+            removed_entities,
+            max_entries: 0,
+            delta: Some(0u32.into()),
+            base_line: 0,
+            updated_base_line: false,
+        })
+    };
+
+    let mut handler = DemoHandler::with_analyser(EntityEvents(Vec::new()));
+    handler.state_handler.server_classes.push(ServerClass {
+        id: ClassId::from(0),
+        name: ServerClassName::from("CTFPlayer"),
+        data_table: "DT_TFPlayer".into(),
+    });
+
+    let packet = |tick: u32, messages: Vec<Message<'static>>| {
+        Packet::Message(MessagePacket {
+            tick: DemoTick::from(tick),
+            messages,
+            meta: MessagePacketMeta::default(),
+        })
+    };
+
+    handler
+        .handle_packet(packet(
+            0,
+            vec![
+                message(vec![entity(1, UpdateType::Enter)], vec![]),
+                message(vec![entity(1, UpdateType::Preserve)], vec![]),
+            ],
+        ))
+        .unwrap();
+    handler
+        .handle_packet(packet(
+            1,
+            vec![
+                message(vec![entity(1, UpdateType::Leave)], vec![]),
+                // entities that were never seen can't be resolved
+                message(vec![entity(2, UpdateType::Delete)], vec![]),
+                message(vec![], vec![EntityId::from(1u32)]),
+            ],
+        ))
+        .unwrap();
+
+    assert_eq!(
+        vec![
+            "enter 1 CTFPlayer",
+            "update 1 0",
+            "end 0",
+            "leave 1 CTFPlayer",
+            "delete 2 unknown",
+            "delete 1 CTFPlayer",
+            "end 1",
+        ],
+        handler.into_output()
+    );
+}
@@ -0,0 +1,111 @@
+use crate::demo::data::DemoTick;
+use crate::demo::header::Header;
+use crate::demo::parser::{DemoParser, DemoTicker, MessageHandler};
+use crate::Result;
+
+/// An index of a demo that allows seeking to any tick without replaying the demo from the start
+///
+/// The index is build in a single pass over the demo, storing the position of the first packet for every tick
+/// and a snapshot of the parser and analyser state every `keyframe_interval` ticks.
+/// Seeking restores the nearest snapshot before the requested tick and replays the demo from there.
+pub struct DemoIndex<'a, A: MessageHandler + Clone> {
+    header: Header,
+    /// The bit position of the first packet of every tick
+    ticks: Vec<(DemoTick, usize)>,
+    keyframes: Vec<Keyframe<'a, A>>,
+}
+
+struct Keyframe<'a, A: MessageHandler + Clone> {
+    tick: DemoTick,
+    /// The bit position of the ticker when the keyframe was taken,
+    /// positions of the cloned ticker are relative to this.
+    position: usize,
+    ticker: DemoTicker<'a, A>,
+}
+
+impl<'a, A: MessageHandler + Clone> DemoIndex<'a, A> {
+    pub fn new(parser: DemoParser<'a, A>, keyframe_interval: u32) -> Result<Self> {
+        let keyframe_interval = keyframe_interval.max(1);
+        let (header, mut ticker) = parser.ticker()?;
+        let mut ticks: Vec<(DemoTick, usize)> = Vec::with_capacity(header.ticks as usize);
+        let mut keyframes: Vec<Keyframe<'a, A>> = Vec::new();
+        let mut next_keyframe = DemoTick::default();
+
+        loop {
+            let pos = ticker.pos();
+            if !ticker.tick()? {
+                break;
+            }
+            let tick = ticker.demo_tick();
+
+            // the signon packets use the server tick, the demo ticks restart from 0 after that
+            if matches!(ticks.last(), Some((last, _)) if tick < *last) {
+                ticks.clear();
+                keyframes.clear();
+                next_keyframe = tick;
+            }
+
+            if !matches!(ticks.last(), Some((last, _)) if *last == tick) {
+                ticks.push((tick, pos));
+            }
+
+            if tick >= next_keyframe {
+                keyframes.push(Keyframe {
+                    tick,
+                    position: ticker.pos(),
+                    ticker: ticker.clone(),
+                });
+                next_keyframe = tick + keyframe_interval;
+            }
+        }
+
+        Ok(DemoIndex {
+            header,
+            ticks,
+            keyframes,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// All ticks in the demo, in order
+    pub fn ticks(&self) -> impl Iterator<Item = DemoTick> + '_ {
+        self.ticks.iter().map(|(tick, _)| *tick)
+    }
+
+    /// The position in bits of the first packet of the tick
+    pub fn packet_position(&self, tick: DemoTick) -> Option<usize> {
+        self.ticks
+            .binary_search_by_key(&tick, |(tick, _)| *tick)
+            .ok()
+            .map(|index| self.ticks[index].1)
+    }
+
+    /// Get a ticker with all packets up to and including `tick` processed
+    ///
+    /// Seeking before the first tick returns the ticker at the first tick,
+    /// seeking past the end of the demo returns the ticker at the end of the demo.
+    ///
+    /// Note that the positions reported by the returned ticker are relative to the keyframe it was restored from.
+    pub fn seek(&self, tick: DemoTick) -> Result<DemoTicker<'a, A>> {
+        let keyframe = self
+            .keyframes
+            .iter()
+            .rev()
+            .find(|keyframe| keyframe.tick <= tick)
+            .or_else(|| self.keyframes.first())
+            .ok_or(crate::ParseError::InvalidDemo("Demo contains no packets"))?;
+        let mut ticker = keyframe.ticker.clone();
+
+        let end = self
+            .ticks
+            .iter()
+            .find(|(packet_tick, _)| *packet_tick > tick)
+            .map(|(_, pos)| pos.saturating_sub(keyframe.position));
+        while !matches!(end, Some(end) if ticker.pos() >= end) && ticker.tick()? {}
+
+        Ok(ticker)
+    }
+}
@@ -0,0 +1,412 @@
+use crate::demo::data::DemoTick;
+use crate::demo::gameevent_gen::{PlayerDeathEvent, PlayerSpawnEvent, PlayerTeamEvent};
+use crate::demo::gamevent::GameEvent;
+use crate::demo::message::gameevent::GameEventMessage;
+use crate::demo::message::packetentities::{EntityId, PacketEntity};
+use crate::demo::message::{Message, MessageType};
+use crate::demo::packet::datatable::{ParseSendTable, ServerClass, ServerClassName};
+use crate::demo::parser::analyser::{Class, Team, UserId};
+use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
+use crate::demo::parser::registry::PlayerRegistry;
+use crate::ParserState;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LifeEnd {
+    /// Killed by another player
+    Killed,
+    /// Killed by themselves or the world
+    Suicide,
+    /// Switched class at a resupply or in the spawn room
+    ClassChange,
+    TeamChange,
+    /// Respawned without dying, e.g. when a new round starts
+    Respawn,
+    Disconnect,
+    /// The demo ended while the player was alive
+    #[default]
+    Unfinished,
+}
+
+/// A single life of a player, from spawning until dying or otherwise leaving the class
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Life {
+    pub user_id: UserId,
+    pub class: Class,
+    pub team: Team,
+    /// For players that were already alive when the demo started, this is the first update of the player resource
+    pub spawn_tick: DemoTick,
+    pub end_tick: DemoTick,
+    pub end: LifeEnd,
+    /// The killing player, for lives ending in `Killed`
+    pub killer: Option<UserId>,
+    /// The weapon that ended the life, for lives ending in `Killed` or `Suicide`
+    pub weapon: Option<String>,
+}
+
+impl Life {
+    pub fn ticks(&self) -> u32 {
+        u32::from(self.end_tick).saturating_sub(u32::from(self.spawn_tick))
+    }
+}
+
+/// The number of seconds a player has been alive on every class and team
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct PlayTime {
+    pub classes: BTreeMap<Class, f32>,
+    pub teams: BTreeMap<Team, f32>,
+}
+
+impl PlayTime {
+    pub fn class(&self, class: Class) -> f32 {
+        self.classes.get(&class).copied().unwrap_or_default()
+    }
+
+    pub fn team(&self, team: Team) -> f32 {
+        self.teams.get(&team).copied().unwrap_or_default()
+    }
+
+    pub fn total(&self) -> f32 {
+        self.classes.values().sum()
+    }
+}
+
+/// Every life in the demo, with the time played on each class and team per player
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LifeState {
+    pub lives: Vec<Life>,
+    pub players: BTreeMap<UserId, PlayTime>,
+    pub interval_per_tick: f32,
+}
+
+impl LifeState {
+    /// Get the number of seconds the player spent alive on the class
+    pub fn class_time(&self, user_id: UserId, class: Class) -> f32 {
+        self.players
+            .get(&user_id)
+            .map(|time| time.class(class))
+            .unwrap_or_default()
+    }
+
+    fn end_life(&mut self, mut life: Life, tick: DemoTick, end: LifeEnd) {
+        life.end_tick = tick;
+        life.end = end;
+        let seconds = life.ticks() as f32 * self.interval_per_tick;
+        let time = self.players.entry(life.user_id).or_default();
+        *time.classes.entry(life.class).or_default() += seconds;
+        *time.teams.entry(life.team).or_default() += seconds;
+        self.lives.push(life);
+    }
+}
+
+/// The class, team and life state of a player slot in the player resource
+#[derive(Default, Debug, Clone, Copy)]
+struct PlayerSlot {
+    class: Class,
+    team: Team,
+    alive: bool,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct LifeAnalyser {
+    state: LifeState,
+    tick: DemoTick,
+    alive: HashMap<UserId, Life>,
+    slots: HashMap<EntityId, PlayerSlot>,
+    resource_seen: bool,
+    class_names: Vec<ServerClassName>, // indexed by ClassId
+}
+
+impl MessageHandler for LifeAnalyser {
+    type Output = LifeState;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        matches!(
+            message_type,
+            MessageType::GameEvent | MessageType::ServerInfo | MessageType::PacketEntities
+        )
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, parser_state: &ParserState) {
+        self.tick = tick;
+        match message {
+            Message::ServerInfo(message) => {
+                self.state.interval_per_tick = message.interval_per_tick
+            }
+            Message::PacketEntities(message) => {
+                for entity in &message.entities {
+                    if self.class_name(entity) == "CTFPlayerResource" {
+                        self.handle_player_resource(entity, parser_state);
+                    }
+                }
+            }
+            Message::GameEvent(GameEventMessage { event, .. }) => match event {
+                GameEvent::PlayerSpawn(event) => self.handle_spawn(event),
+                GameEvent::PlayerDeath(event) => self.handle_death(event),
+                GameEvent::PlayerTeam(event) => self.handle_team(event),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn handle_data_tables(
+        &mut self,
+        _parse_tables: &[ParseSendTable],
+        server_classes: &[ServerClass],
+        _parser_state: &ParserState,
+    ) {
+        self.class_names = server_classes
+            .iter()
+            .map(|class| &class.name)
+            .cloned()
+            .collect();
+    }
+
+    fn into_output(mut self, _state: &ParserState) -> Self::Output {
+        let mut alive: Vec<Life> = self.alive.into_values().collect();
+        alive.sort_by_key(|life| (life.spawn_tick, life.user_id));
+        for life in alive {
+            self.state.end_life(life, self.tick, LifeEnd::Unfinished);
+        }
+        self.state
+    }
+}
+
+impl BorrowMessageHandler for LifeAnalyser {
+    type BorrowedOutput<'a> = &'a Self::Output;
+
+    fn borrow_output<'a>(&'a self, _state: &ParserState) -> Self::BorrowedOutput<'a> {
+        &self.state
+    }
+}
+
+impl LifeAnalyser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn class_name(&self, entity: &PacketEntity) -> &str {
+        self.class_names
+            .get(usize::from(entity.server_class))
+            .map(|class_name| class_name.as_str())
+            .unwrap_or("")
+    }
+
+    fn handle_player_resource(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        for prop in entity.props(parser_state) {
+            if let Some((table_name, prop_name)) = prop.identifier.names() {
+                if let Ok(player_id) = u32::from_str(prop_name.as_str()) {
+                    let slot = self.slots.entry(EntityId::from(player_id)).or_default();
+                    let value = i64::try_from(&prop.value).unwrap_or_default();
+                    match table_name.as_str() {
+                        "m_iPlayerClass" => slot.class = Class::new(value),
+                        "m_iTeam" => slot.team = Team::new(value),
+                        "m_bAlive" => slot.alive = value != 0,
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if !self.resource_seen {
+            self.resource_seen = true;
+            self.start_existing_lives(&parser_state.players);
+        }
+    }
+
+    /// Players that are alive in the first update of the player resource spawned before the demo started
+    fn start_existing_lives(&mut self, players: &PlayerRegistry) {
+        for (entity_id, slot) in &self.slots {
+            if !slot.alive || slot.class == Class::Other || !slot.team.is_player() {
+                continue;
+            }
+            if let Some(user_id) = players.user_id(*entity_id) {
+                self.alive.entry(user_id).or_insert(Life {
+                    user_id,
+                    class: slot.class,
+                    team: slot.team,
+                    spawn_tick: self.tick,
+                    end_tick: self.tick,
+                    end: LifeEnd::Unfinished,
+                    killer: None,
+                    weapon: None,
+                });
+            }
+        }
+    }
+
+    fn handle_spawn(&mut self, event: &PlayerSpawnEvent) {
+        let user_id = UserId::from(event.user_id);
+        let class = Class::new(event.class);
+        let team = Team::new(event.team);
+        // players get an initial spawn without a class when joining a team
+        if class == Class::Other {
+            return;
+        }
+
+        if let Some(life) = self.alive.remove(&user_id) {
+            if life.spawn_tick == self.tick && life.class == class && life.team == team {
+                // the game can send a duplicate spawn event
+                self.alive.insert(user_id, life);
+                return;
+            }
+            // changing class at a resupply respawns the player without a death
+            let end = if life.class != class {
+                LifeEnd::ClassChange
+            } else if life.team != team {
+                LifeEnd::TeamChange
+            } else {
+                LifeEnd::Respawn
+            };
+            self.state.end_life(life, self.tick, end);
+        }
+
+        self.alive.insert(
+            user_id,
+            Life {
+                user_id,
+                class,
+                team,
+                spawn_tick: self.tick,
+                end_tick: self.tick,
+                end: LifeEnd::Unfinished,
+                killer: None,
+                weapon: None,
+            },
+        );
+    }
+
+    fn handle_death(&mut self, event: &PlayerDeathEvent) {
+        const FEIGN_DEATH: u16 = 0x20;
+
+        if event.death_flags & FEIGN_DEATH != 0 {
+            return;
+        }
+
+        // lives of players missing from the player resource at the start of the demo are unknown
+        let user_id = UserId::from(event.user_id);
+        if let Some(mut life) = self.alive.remove(&user_id) {
+            let end = if event.attacker == 0 || event.attacker == event.user_id {
+                LifeEnd::Suicide
+            } else {
+                life.killer = Some(UserId::from(event.attacker));
+                LifeEnd::Killed
+            };
+            life.weapon = Some(event.weapon.to_string());
+            self.state.end_life(life, self.tick, end);
+        }
+    }
+
+    fn handle_team(&mut self, event: &PlayerTeamEvent) {
+        let user_id = UserId::from(event.user_id);
+        if let Some(life) = self.alive.remove(&user_id) {
+            let end = if event.disconnect {
+                LifeEnd::Disconnect
+            } else {
+                LifeEnd::TeamChange
+            };
+            self.state.end_life(life, self.tick, end);
+        }
+    }
+}
+
+#[test]
+fn test_class_time() {
+    use crate::demo::parser::test_util::{event_message as event, *};
+    use crate::demo::sendprop::SendPropValue;
+
+    let mut parser_state = parser_state();
+    for index in 0..3 {
+        add_user(&mut parser_state, index, index as u16 + 1);
+    }
+
+    let mut analyser = LifeAnalyser::new();
+    analyser.handle_data_tables(&[], &server_classes(&["CTFPlayerResource"]), &parser_state);
+
+    // only the sniper in slot 3 is alive when the demo starts
+    let resource = |props: Vec<(&str, &str, i64)>| {
+        let props = props
+            .into_iter()
+            .map(|(table, name, value)| (table, name, SendPropValue::Integer(value)))
+            .collect();
+        entities_message(vec![packet_entity(0, 40, props)])
+    };
+    let spawn = |user_id: u16, class: Class| {
+        event(GameEvent::PlayerSpawn(PlayerSpawnEvent {
+            user_id,
+            team: Team::Red as u16,
+            class: class as u16,
+        }))
+    };
+    let death = PlayerDeathEvent {
+        victim_ent_index: 3,
+        inflictor_ent_index: 1,
+        weapon_def_index: 18,
+        ..death_event(3, 1, "tf_projectile_rocket")
+    };
+
+    let messages = [
+        (0, server_info_message("cp_process_final")),
+        (
+            50,
+            resource(vec![
+                ("m_bAlive", "001", 0),
+                ("m_bAlive", "003", 1),
+                ("m_iPlayerClass", "003", Class::Sniper as i64),
+                ("m_iTeam", "003", Team::Blue as i64),
+            ]),
+        ),
+        (100, spawn(1, Class::Soldier)),
+        (100, spawn(2, Class::Medic)),
+        // switch to demoman at a resupply
+        (300, spawn(1, Class::Demoman)),
+        (400, event(GameEvent::PlayerDeath(Box::new(death)))),
+        (
+            500,
+            event(GameEvent::PlayerTeam(PlayerTeamEvent {
+                user_id: 2,
+                team: 0,
+                old_team: Team::Red as u8,
+                disconnect: true,
+                auto_team: false,
+                silent: false,
+                name: Default::default(),
+            })),
+        ),
+        (700, resource(vec![("m_bAlive", "003", 0)])),
+    ];
+    for (tick, message) in messages {
+        analyser.handle_message(&message, DemoTick::from(tick), &parser_state);
+    }
+
+    let state = analyser.into_output(&parser_state);
+    let ends: Vec<_> = state
+        .lives
+        .iter()
+        .map(|life| (life.user_id, life.class, life.ticks(), life.end))
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                UserId::from(1u16),
+                Class::Soldier,
+                200,
+                LifeEnd::ClassChange
+            ),
+            (UserId::from(3u16), Class::Sniper, 350, LifeEnd::Killed),
+            (UserId::from(2u16), Class::Medic, 400, LifeEnd::Disconnect),
+            (UserId::from(1u16), Class::Demoman, 400, LifeEnd::Unfinished),
+        ],
+        ends
+    );
+    assert_eq!(Some(UserId::from(1u16)), state.lives[1].killer);
+    assert!((state.class_time(UserId::from(1u16), Class::Soldier) - 3.0).abs() < 0.001);
+    assert!((state.class_time(UserId::from(1u16), Class::Demoman) - 6.0).abs() < 0.001);
+    assert!((state.players[&UserId::from(1u16)].team(Team::Red) - 9.0).abs() < 0.001);
+    assert!((state.players[&UserId::from(3u16)].team(Team::Blue) - 5.25).abs() < 0.001);
+}
@@ -0,0 +1,423 @@
+use crate::demo::data::DemoTick;
+use crate::demo::gameevent_gen::PlayerChargeDeployedEvent;
+use crate::demo::gamevent::GameEvent;
+use crate::demo::message::gameevent::GameEventMessage;
+use crate::demo::message::packetentities::{EntityId, PacketEntity};
+use crate::demo::message::{Message, MessageType};
+use crate::demo::packet::datatable::{ParseSendTable, ServerClass, ServerClassName};
+use crate::demo::parser::analyser::{Class, Team, UserId};
+use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
+use crate::demo::parser::registry::PlayerRegistry;
+use crate::demo::sendprop::SendPropIdentifier;
+use crate::ParserState;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// Charge percentage from which dying counts as a near-full drop
+pub const NEAR_FULL_CHARGE: u8 = 95;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Medigun {
+    #[default]
+    Uber,
+    Kritzkrieg,
+    QuickFix,
+    Vaccinator,
+}
+
+impl Medigun {
+    pub fn from_item_definition(index: i64) -> Self {
+        match index {
+            35 => Medigun::Kritzkrieg,
+            411 => Medigun::QuickFix,
+            998 => Medigun::Vaccinator,
+            _ => Medigun::Uber,
+        }
+    }
+}
+
+/// The building of a single charge, from empty to full
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChargeBuild {
+    pub start_tick: DemoTick,
+    pub end_tick: DemoTick,
+    pub medigun: Medigun,
+}
+
+impl ChargeBuild {
+    pub fn ticks(&self) -> u32 {
+        u32::from(self.end_tick).saturating_sub(self.start_tick.into())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChargeDeploy {
+    pub tick: DemoTick,
+    pub medigun: Medigun,
+    pub target: UserId,
+}
+
+/// A medic dying while having (almost) full charge
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChargeDrop {
+    pub tick: DemoTick,
+    pub medigun: Medigun,
+    pub charge: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct MedicStats {
+    pub builds: Vec<ChargeBuild>,
+    pub deploys: Vec<ChargeDeploy>,
+    /// Deaths with a full charge
+    pub drops: Vec<ChargeDrop>,
+    /// Deaths with at least [`NEAR_FULL_CHARGE`] but less than full charge
+    pub near_full_drops: Vec<ChargeDrop>,
+}
+
+/// The highest charge of the medics of both teams, recorded every time it changes
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChargeAdvantage {
+    pub tick: DemoTick,
+    pub red: u8,
+    pub blue: u8,
+}
+
+impl ChargeAdvantage {
+    /// The charge advantage of red over blue, in percent
+    pub fn advantage(&self) -> i16 {
+        self.red as i16 - self.blue as i16
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct MedicState {
+    pub medics: BTreeMap<UserId, MedicStats>,
+    pub advantage: Vec<ChargeAdvantage>,
+}
+
+/// The charge state of a single player entity
+#[derive(Debug, Clone, Default)]
+struct ChargeTracker {
+    team: Team,
+    class: Class,
+    charge: u8,
+    medigun: Medigun,
+    build_start: Option<DemoTick>,
+    deployed: bool,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct MedicAnalyser {
+    state: MedicState,
+    players: BTreeMap<EntityId, ChargeTracker>,
+    mediguns: HashMap<EntityId, EntityId>,
+    class_names: Vec<ServerClassName>, // indexed by ClassId
+}
+
+impl MessageHandler for MedicAnalyser {
+    type Output = MedicState;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        matches!(
+            message_type,
+            MessageType::PacketEntities | MessageType::GameEvent
+        )
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, parser_state: &ParserState) {
+        match message {
+            Message::PacketEntities(message) => {
+                for entity in &message.entities {
+                    self.handle_entity(entity, tick, parser_state);
+                }
+            }
+            Message::GameEvent(GameEventMessage { event, .. }) => match event {
+                GameEvent::PlayerChargeDeployed(event) => {
+                    self.handle_deploy(event, tick, &parser_state.players)
+                }
+                GameEvent::PlayerDeath(event) => {
+                    self.handle_death(event.user_id, tick, &parser_state.players)
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn handle_data_tables(
+        &mut self,
+        _parse_tables: &[ParseSendTable],
+        server_classes: &[ServerClass],
+        _parser_state: &ParserState,
+    ) {
+        self.class_names = server_classes
+            .iter()
+            .map(|class| &class.name)
+            .cloned()
+            .collect();
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self::Output {
+        self.state
+    }
+}
+
+impl BorrowMessageHandler for MedicAnalyser {
+    type BorrowedOutput<'a> = &'a Self::Output;
+
+    fn borrow_output<'a>(&'a self, _state: &ParserState) -> Self::BorrowedOutput<'a> {
+        &self.state
+    }
+}
+
+impl MedicAnalyser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn handle_entity(&mut self, entity: &PacketEntity, tick: DemoTick, parser_state: &ParserState) {
+        let class_name: &str = self
+            .class_names
+            .get(usize::from(entity.server_class))
+            .map(|class_name| class_name.as_str())
+            .unwrap_or("");
+        match class_name {
+            "CTFPlayerResource" => self.handle_player_resource(entity, tick, parser_state),
+            "CWeaponMedigun" => self.handle_medigun(entity, parser_state),
+            _ => {}
+        }
+    }
+
+    fn handle_player_resource(
+        &mut self,
+        entity: &PacketEntity,
+        tick: DemoTick,
+        parser_state: &ParserState,
+    ) {
+        let mut charge_changed = false;
+        for prop in entity.props(parser_state) {
+            if let Some((table_name, prop_name)) = prop.identifier.names() {
+                if let Ok(player_id) = u32::from_str(prop_name.as_str()) {
+                    let entity_id = EntityId::from(player_id);
+                    let value = i64::try_from(&prop.value).unwrap_or_default();
+                    match table_name.as_str() {
+                        "m_iTeam" => {
+                            self.players.entry(entity_id).or_default().team = Team::new(value)
+                        }
+                        "m_iPlayerClass" => {
+                            self.players.entry(entity_id).or_default().class = Class::new(value)
+                        }
+                        "m_iChargeLevel" => {
+                            self.update_charge(entity_id, value as u8, tick, &parser_state.players);
+                            charge_changed = true;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if charge_changed {
+            self.update_advantage(tick);
+        }
+    }
+
+    fn handle_medigun(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const OWNER: SendPropIdentifier =
+            SendPropIdentifier::new("DT_BaseCombatWeapon", "m_hOwner");
+        const ITEM_DEFINITION: SendPropIdentifier =
+            SendPropIdentifier::new("DT_ScriptCreatedItem", "m_iItemDefinitionIndex");
+
+        let mut item_definition = None;
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                OWNER => {
+                    // the lower 11 bits of the handle are the entity index
+                    let handle = i64::try_from(&prop.value).unwrap_or_default();
+                    self.mediguns
+                        .insert(entity.entity_index, EntityId::from((handle & 0x7FF) as u32));
+                }
+                ITEM_DEFINITION => {
+                    item_definition = Some(i64::try_from(&prop.value).unwrap_or_default())
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(item_definition), Some(owner)) =
+            (item_definition, self.mediguns.get(&entity.entity_index))
+        {
+            self.players.entry(*owner).or_default().medigun =
+                Medigun::from_item_definition(item_definition);
+        }
+    }
+
+    fn update_charge(
+        &mut self,
+        entity_id: EntityId,
+        charge: u8,
+        tick: DemoTick,
+        players: &PlayerRegistry,
+    ) {
+        let player = self.players.entry(entity_id).or_default();
+        let old_charge = player.charge;
+        player.charge = charge;
+
+        if charge == 0 {
+            player.deployed = false;
+            player.build_start = None;
+        } else if old_charge == 0 && !player.deployed {
+            player.build_start = Some(tick);
+        }
+
+        if charge >= 100 && old_charge < 100 {
+            if let (Some(start_tick), Some(user_id)) =
+                (player.build_start.take(), players.user_id(entity_id))
+            {
+                let build = ChargeBuild {
+                    start_tick,
+                    end_tick: tick,
+                    medigun: player.medigun,
+                };
+                self.state
+                    .medics
+                    .entry(user_id)
+                    .or_default()
+                    .builds
+                    .push(build);
+            }
+        }
+    }
+
+    fn update_advantage(&mut self, tick: DemoTick) {
+        let team_charge = |team: Team| {
+            self.players
+                .values()
+                .filter(|player| player.team == team && player.class == Class::Medic)
+                .map(|player| player.charge)
+                .max()
+                .unwrap_or_default()
+        };
+        let (red, blue) = (team_charge(Team::Red), team_charge(Team::Blue));
+
+        if !matches!(self.state.advantage.last(), Some(last) if last.red == red && last.blue == blue)
+        {
+            self.state
+                .advantage
+                .push(ChargeAdvantage { tick, red, blue });
+        }
+    }
+
+    fn player_by_user(
+        &mut self,
+        players: &PlayerRegistry,
+        user_id: u16,
+    ) -> Option<&mut ChargeTracker> {
+        let entity_id = players.entity_id(UserId::from(user_id))?;
+        self.players.get_mut(&entity_id)
+    }
+
+    fn handle_deploy(
+        &mut self,
+        event: &PlayerChargeDeployedEvent,
+        tick: DemoTick,
+        players: &PlayerRegistry,
+    ) {
+        let medigun = match self.player_by_user(players, event.user_id) {
+            Some(player) => {
+                player.deployed = true;
+                player.build_start = None;
+                player.medigun
+            }
+            None => Medigun::default(),
+        };
+        self.state
+            .medics
+            .entry(UserId::from(event.user_id))
+            .or_default()
+            .deploys
+            .push(ChargeDeploy {
+                tick,
+                medigun,
+                target: UserId::from(event.target_id),
+            });
+    }
+
+    fn handle_death(&mut self, user_id: u16, tick: DemoTick, players: &PlayerRegistry) {
+        let (charge, medigun) = match self.player_by_user(players, user_id) {
+            Some(player) if player.class == Class::Medic && !player.deployed => {
+                (player.charge, player.medigun)
+            }
+            _ => return,
+        };
+
+        let drop = ChargeDrop {
+            tick,
+            medigun,
+            charge,
+        };
+        let medic = self.state.medics.entry(UserId::from(user_id)).or_default();
+        if charge >= 100 {
+            medic.drops.push(drop);
+        } else if charge >= NEAR_FULL_CHARGE {
+            medic.near_full_drops.push(drop);
+        }
+    }
+}
+
+#[test]
+fn test_charge_drops() {
+    use crate::demo::gameevent_gen::PlayerDeathEvent;
+    use crate::demo::parser::test_util::*;
+    use crate::demo::sendprop::SendPropValue;
+
+    let medic = UserId::from(7u16);
+    let mut parser_state = parser_state();
+    add_user(&mut parser_state, 0, 7);
+
+    let mut analyser = MedicAnalyser::new();
+    analyser.handle_data_tables(&[], &server_classes(&["CTFPlayerResource"]), &parser_state);
+
+    let resource = |props: Vec<(&str, i64)>| {
+        let props = props
+            .into_iter()
+            .map(|(table, value)| (table, "001", SendPropValue::Integer(value)))
+            .collect();
+        entities_message(vec![packet_entity(0, 40, props)])
+    };
+    let death = event_message(GameEvent::PlayerDeath(Box::new(PlayerDeathEvent {
+        victim_ent_index: 1,
+        ..death_event(7, 0, "world")
+    })));
+
+    let messages = [
+        (
+            10,
+            resource(vec![
+                ("m_iTeam", Team::Red as i64),
+                ("m_iPlayerClass", Class::Medic as i64),
+                ("m_iChargeLevel", 0),
+            ]),
+        ),
+        (20, resource(vec![("m_iChargeLevel", 50)])),
+        (30, resource(vec![("m_iChargeLevel", 100)])),
+        (40, death),
+    ];
+    for (tick, message) in messages {
+        analyser.handle_message(&message, DemoTick::from(tick), &parser_state);
+    }
+    let state = analyser.into_output(&parser_state);
+
+    let stats = &state.medics[&medic];
+    assert_eq!(1, stats.builds.len());
+    assert_eq!(DemoTick::from(20), stats.builds[0].start_tick);
+    assert_eq!(DemoTick::from(30), stats.builds[0].end_tick);
+    assert_eq!(1, stats.drops.len());
+    assert_eq!(100, stats.drops[0].charge);
+    assert_eq!(DemoTick::from(40), stats.drops[0].tick);
+    assert_eq!(100, state.advantage.last().unwrap().red);
+}
@@ -8,17 +8,37 @@ use crate::demo::header::Header;
 use crate::demo::packet::Packet;
 use crate::demo::parser::analyser::Analyser;
 pub use crate::demo::parser::analyser::MatchState;
-pub use crate::demo::parser::handler::{DemoHandler, MessageHandler, NullHandler};
+pub use crate::demo::parser::entitystore::{EntityState, EntityStore};
+pub use crate::demo::parser::handler::{
+    DemoHandler, MessageHandler, NullHandler, ParserCheckpoint,
+};
+pub use crate::demo::parser::index::DemoIndex;
+pub use crate::demo::parser::registry::{PlayerIdentity, PlayerRegistry};
 pub use crate::demo::parser::state::ParserState;
-use crate::Stream;
+use crate::{Demo, Stream};
+use rayon::prelude::*;
+use std::fs;
+use std::path::Path;
 
 pub mod analyser;
+pub mod damageanalyser;
+pub mod entitystore;
 pub mod error;
+pub mod flaganalyser;
 pub mod gamestateanalyser;
 pub mod handler;
+pub mod index;
+pub mod lifeanalyser;
+pub mod medicanalyser;
 pub mod messagetypeanalyser;
+pub mod mvmanalyser;
+pub mod objectiveanalyser;
 pub mod player_summary_analyzer;
+pub mod registry;
 pub mod state;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub mod timelineanalyser;
 
 pub use self::error::*;
 use crate::demo::parser::handler::BorrowMessageHandler;
@@ -87,6 +107,12 @@ impl<'a, A: MessageHandler> DemoParser<'a, A> {
         }
     }
 
+    /// Keep track of the props of all entities in [`ParserState::entity_store`]
+    pub fn with_entity_store(mut self) -> Self {
+        self.handler.enable_entity_store();
+        self
+    }
+
     pub fn parse(self) -> Result<(Header, A::Output)> {
         let (header, mut ticker) = self.ticker()?;
         while ticker.tick()? {
@@ -95,6 +121,45 @@ impl<'a, A: MessageHandler> DemoParser<'a, A> {
         Ok((header, ticker.into_state()))
     }
 
+    /// Parse the demo, keeping the output gathered so far if the demo is truncated or an error occurs
+    ///
+    /// Only errors while reading the header are returned as error,
+    /// any later error is returned in the diagnostics together with the output up to that point.
+    pub fn parse_partial(self) -> Result<(Header, A::Output, ParseDiagnostics)> {
+        let (header, mut ticker) = self.ticker()?;
+        let mut diagnostics = ParseDiagnostics {
+            byte_offset: ticker.pos() / 8,
+            ..ParseDiagnostics::default()
+        };
+        loop {
+            match ticker.tick() {
+                Ok(true) => {
+                    diagnostics.last_tick = ticker.demo_tick();
+                    diagnostics.byte_offset = ticker.pos() / 8;
+                }
+                Ok(false) => break,
+                Err(e) => {
+                    diagnostics.error = Some(e);
+                    break;
+                }
+            }
+        }
+        diagnostics.incomplete = ticker.packets.incomplete || diagnostics.error.is_some();
+        Ok((header, ticker.into_state(), diagnostics))
+    }
+
+    /// Resume parsing from a checkpoint
+    ///
+    /// The stream should contain the same demo the checkpoint was created from, including the header.
+    pub fn resume(mut self, checkpoint: ParserCheckpoint) -> Result<DemoTicker<'a, A>> {
+        self.stream.set_pos(checkpoint.position)?;
+        self.handler.restore_checkpoint(checkpoint);
+        Ok(DemoTicker {
+            handler: self.handler,
+            packets: RawPacketStream::new(self.stream),
+        })
+    }
+
     /// A Ticker provides a way to step trough the demo packet by packet
     /// while allowing to see the intermediate states
     pub fn ticker(mut self) -> Result<(Header, DemoTicker<'a, A>)> {
@@ -108,6 +173,49 @@ impl<'a, A: MessageHandler> DemoParser<'a, A> {
     }
 }
 
+/// Information about how far a demo was parsed, returned by [`DemoParser::parse_partial`]
+#[derive(Debug, Default)]
+pub struct ParseDiagnostics {
+    /// Whether the parser stopped before reaching the end of the demo
+    pub incomplete: bool,
+    /// The tick of the last packet that was successfully parsed
+    pub last_tick: DemoTick,
+    /// The offset in bytes of the end of the last packet that was successfully parsed
+    pub byte_offset: usize,
+    /// The error that stopped the parser, if the demo wasn't just truncated
+    pub error: Option<ParseError>,
+}
+
+/// Parse multiple demo files in parallel
+///
+/// The files are parsed on the global rayon thread pool, with a new analyser created by `analyser` for every file.
+/// The results are returned in the same order as the paths, a file failing to read or parse doesn't affect the other files.
+/// With `parse_all` every message is parsed, the same as the python bindings, otherwise only the messages handled by the analyser.
+pub fn parse_many<A, F, P>(
+    paths: &[P],
+    analyser: F,
+    parse_all: bool,
+) -> Vec<Result<(Header, A::Output)>>
+where
+    A: MessageHandler,
+    A::Output: Send,
+    F: Fn() -> A + Sync,
+    P: AsRef<Path> + Sync,
+{
+    paths
+        .par_iter()
+        .map(|path| {
+            let file = fs::read(path)?;
+            let demo = Demo::new(&file);
+            if parse_all {
+                DemoParser::new_all_with_analyser(demo.get_stream(), analyser()).parse()
+            } else {
+                DemoParser::new_with_analyser(demo.get_stream(), analyser()).parse()
+            }
+        })
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct RawPacketStream<'a> {
     stream: Stream<'a>,
@@ -177,10 +285,30 @@ impl<'a, A: MessageHandler> DemoTicker<'a, A> {
     pub fn into_state(self) -> A::Output {
         self.handler.into_output()
     }
+
+    /// The position in bits of the next packet in the demo
+    pub fn pos(&self) -> usize {
+        self.packets.pos()
+    }
+
+    /// The tick of the last processed packet
+    pub fn demo_tick(&self) -> DemoTick {
+        self.handler.demo_tick
+    }
+
+    /// Whether the demo ended without a stop packet
+    pub fn incomplete(&self) -> bool {
+        self.packets.incomplete
+    }
+
+    /// Create a checkpoint that parsing can be resumed from with [`DemoParser::resume`]
+    pub fn checkpoint(&self) -> ParserCheckpoint {
+        self.handler.checkpoint(self.pos())
+    }
 }
 
 impl<'a, A: MessageHandler + BorrowMessageHandler> DemoTicker<'a, A> {
-    pub fn state(&self) -> &A::Output {
+    pub fn state(&self) -> A::BorrowedOutput<'_> {
         self.handler.borrow_output()
     }
 
@@ -189,7 +317,7 @@ impl<'a, A: MessageHandler + BorrowMessageHandler> DemoTicker<'a, A> {
     }
 
     /// Process the next packet
-    pub fn next(&mut self) -> Result<Option<Tick<A::Output>>> {
+    pub fn next(&mut self) -> Result<Option<Tick<A::BorrowedOutput<'_>>>> {
         Ok(
             if let Some(packet) = self.packets.next(&self.handler.state_handler)? {
                 let tick = packet.tick();
@@ -208,7 +336,7 @@ impl<'a, A: MessageHandler + BorrowMessageHandler> DemoTicker<'a, A> {
 }
 
 pub struct Tick<'a, State> {
-    pub state: &'a State,
+    pub state: State,
     pub parser_state: &'a ParserState,
     pub tick: DemoTick,
 }
@@ -0,0 +1,423 @@
+use crate::demo::data::DemoTick;
+use crate::demo::gamevent::GameEvent;
+use crate::demo::message::gameevent::GameEventMessage;
+use crate::demo::message::packetentities::{EntityId, PacketEntity};
+use crate::demo::message::{Message, MessageType};
+use crate::demo::packet::datatable::{ParseSendTable, ServerClass, ServerClassName};
+use crate::demo::parser::analyser::UserId;
+use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
+use crate::demo::parser::registry::PlayerRegistry;
+use crate::demo::sendprop::SendPropIdentifier;
+use crate::demo::vector::Vector;
+use crate::ParserState;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WaveOutcome {
+    Completed,
+    Failed,
+    /// The demo ended during the wave
+    #[default]
+    InProgress,
+}
+
+/// Stats for a single player
+///
+/// Missed credits are only tracked for the whole team in `Wave::credits_missed`, a currency pack
+/// has no owner until it's collected so there is no player to attribute missed credits to.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct MvmPlayerStats {
+    pub credits_collected: u32,
+    pub bomb_resets: u32,
+    /// Robots killed while delivering the bomb
+    pub bomb_deliveries_stopped: u32,
+    pub canteens_used: u32,
+    pub quick_sentry_upgrades: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BusterDetonation {
+    pub tick: DemoTick,
+    pub position: Vector,
+}
+
+/// A single attempt at a wave, failed waves are retried as a new attempt with the same index
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Wave {
+    /// The wave number, starting at 1
+    pub index: u16,
+    pub max_waves: u16,
+    pub start_tick: DemoTick,
+    pub end_tick: Option<DemoTick>,
+    pub outcome: WaveOutcome,
+    /// The credits dropped by robots and tanks
+    ///
+    /// The value of a currency pack isn't networked, so this is derived from the money on the ground
+    /// and the collected credits. Money that disappears without being collected isn't counted.
+    pub credits_dropped: u32,
+    pub credits_collected: u32,
+    /// The dropped credits that weren't collected, for the whole team
+    pub credits_missed: u32,
+    pub bomb_resets: u32,
+    pub bomb_carriers_killed: u32,
+    pub tanks_destroyed: u32,
+    pub sentry_busters_killed: u32,
+    pub buster_detonations: Vec<BusterDetonation>,
+    /// The number of upgrades bought, the game doesn't include which player bought the upgrade
+    pub upgrades: u32,
+    pub players: BTreeMap<UserId, MvmPlayerStats>,
+}
+
+/// Stats for a Mann vs Machine mission
+///
+/// Events between waves are counted towards the previous wave.
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MvmState {
+    /// The name of the population file of the mission
+    pub mission: String,
+    pub completed: bool,
+    pub waves: Vec<Wave>,
+    /// Totals over all waves
+    pub players: BTreeMap<UserId, MvmPlayerStats>,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct MvmAnalyser {
+    state: MvmState,
+    tick: DemoTick,
+    world_money: u32,
+    /// Collected credits that aren't removed from the money on the ground yet
+    pending_pickups: u32,
+    /// Decrease of the money on the ground during the current tick that isn't explained by a pickup yet
+    unexplained_decrease: (DemoTick, u32),
+    class_names: Vec<ServerClassName>, // indexed by ClassId
+}
+
+impl MessageHandler for MvmAnalyser {
+    type Output = MvmState;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        matches!(
+            message_type,
+            MessageType::PacketEntities | MessageType::GameEvent
+        )
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, parser_state: &ParserState) {
+        self.tick = tick;
+        match message {
+            Message::PacketEntities(message) => {
+                for entity in &message.entities {
+                    self.handle_entity(entity, parser_state);
+                }
+            }
+            Message::GameEvent(GameEventMessage { event, .. }) => {
+                self.handle_event(event, &parser_state.players)
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_data_tables(
+        &mut self,
+        _parse_tables: &[ParseSendTable],
+        server_classes: &[ServerClass],
+        _parser_state: &ParserState,
+    ) {
+        self.class_names = server_classes
+            .iter()
+            .map(|class| &class.name)
+            .cloned()
+            .collect();
+    }
+
+    fn into_output(mut self, _state: &ParserState) -> Self::Output {
+        for wave in self.state.waves.iter_mut() {
+            wave.credits_missed = wave.credits_dropped.saturating_sub(wave.credits_collected);
+        }
+        self.state
+    }
+}
+
+impl BorrowMessageHandler for MvmAnalyser {
+    type BorrowedOutput<'a> = &'a Self::Output;
+
+    fn borrow_output<'a>(&'a self, _state: &ParserState) -> Self::BorrowedOutput<'a> {
+        &self.state
+    }
+}
+
+impl MvmAnalyser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn handle_entity(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        const POPFILE: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFObjectiveResource", "m_iszMvMPopfileName");
+        const WORLD_MONEY: SendPropIdentifier =
+            SendPropIdentifier::new("DT_TFObjectiveResource", "m_nMvMWorldMoney");
+
+        let class_name: &str = self
+            .class_names
+            .get(usize::from(entity.server_class))
+            .map(|class_name| class_name.as_str())
+            .unwrap_or("");
+        if class_name != "CTFObjectiveResource" {
+            return;
+        }
+
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                POPFILE => {
+                    if let Ok(popfile) = <&str>::try_from(&prop.value) {
+                        self.state.mission = popfile.to_string();
+                    }
+                }
+                WORLD_MONEY => {
+                    let money = i64::try_from(&prop.value).unwrap_or_default() as u32;
+                    self.handle_world_money(money);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The money on the ground increases by the dropped credits and decreases by the collected credits,
+    /// add back the pickups since the last update so drops and pickups in the same update don't cancel out
+    fn handle_world_money(&mut self, money: u32) {
+        let change = money as i64 - self.world_money as i64 + self.pending_pickups as i64;
+        self.world_money = money;
+        self.pending_pickups = 0;
+        if change > 0 {
+            if let Some(wave) = self.state.waves.last_mut() {
+                wave.credits_dropped += change as u32;
+            }
+        } else if change < 0 {
+            // the pickup event can come after the entity update in the same tick
+            self.unexplained_decrease = (self.tick, (-change) as u32);
+        }
+    }
+
+    fn handle_pickup(&mut self, currency: u32) {
+        let (tick, decrease) = &mut self.unexplained_decrease;
+        let explained = if *tick == self.tick {
+            currency.min(*decrease)
+        } else {
+            0
+        };
+        *decrease -= explained;
+        self.pending_pickups += currency - explained;
+    }
+
+    fn handle_event(&mut self, event: &GameEvent, players: &PlayerRegistry) {
+        match event {
+            GameEvent::MvmBeginWave(event) => {
+                self.pending_pickups = 0;
+                self.state.waves.push(Wave {
+                    index: event.wave_index + 1,
+                    max_waves: event.max_waves,
+                    start_tick: self.tick,
+                    ..Wave::default()
+                });
+            }
+            GameEvent::MvmWaveComplete(_) => self.end_wave(WaveOutcome::Completed),
+            GameEvent::MvmWaveFailed(_) => self.end_wave(WaveOutcome::Failed),
+            GameEvent::MvmMissionComplete(event) => {
+                self.state.completed = true;
+                if self.state.mission.is_empty() {
+                    self.state.mission = event.mission.to_string();
+                }
+            }
+            GameEvent::MvmPickupCurrency(event) => {
+                let currency = event.currency as u32;
+                self.handle_pickup(currency);
+                if let Some(wave) = self.state.waves.last_mut() {
+                    wave.credits_collected += currency;
+                }
+                self.player_stats(players, event.player, |stats| {
+                    stats.credits_collected += currency
+                });
+            }
+            GameEvent::MvmBombResetByPlayer(event) => {
+                if let Some(wave) = self.state.waves.last_mut() {
+                    wave.bomb_resets += 1;
+                }
+                self.player_stats(players, event.player, |stats| stats.bomb_resets += 1);
+            }
+            GameEvent::MvmKillRobotDeliveringBomb(event) => {
+                self.player_stats(players, event.player, |stats| {
+                    stats.bomb_deliveries_stopped += 1
+                });
+            }
+            GameEvent::MvmQuickSentryUpgrade(event) => {
+                self.player_stats(players, event.player, |stats| {
+                    stats.quick_sentry_upgrades += 1
+                });
+            }
+            GameEvent::PlayerUsedPowerUpBottle(event) => {
+                self.player_stats(players, event.player, |stats| stats.canteens_used += 1);
+            }
+            GameEvent::MvmBombCarrierKilled(_) => {
+                if let Some(wave) = self.state.waves.last_mut() {
+                    wave.bomb_carriers_killed += 1;
+                }
+            }
+            GameEvent::MvmTankDestroyedByPlayers(_) => {
+                if let Some(wave) = self.state.waves.last_mut() {
+                    wave.tanks_destroyed += 1;
+                }
+            }
+            GameEvent::MvmSentryBusterKilled(_) => {
+                if let Some(wave) = self.state.waves.last_mut() {
+                    wave.sentry_busters_killed += 1;
+                }
+            }
+            GameEvent::MvmSentryBusterDetonate(event) => {
+                let tick = self.tick;
+                if let Some(wave) = self.state.waves.last_mut() {
+                    wave.buster_detonations.push(BusterDetonation {
+                        tick,
+                        position: Vector {
+                            x: event.det_x,
+                            y: event.det_y,
+                            z: event.det_z,
+                        },
+                    });
+                }
+            }
+            GameEvent::PlayerUpgraded(_) => {
+                if let Some(wave) = self.state.waves.last_mut() {
+                    wave.upgrades += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end_wave(&mut self, outcome: WaveOutcome) {
+        let tick = self.tick;
+        if let Some(wave) = self
+            .state
+            .waves
+            .last_mut()
+            .filter(|wave| wave.outcome == WaveOutcome::InProgress)
+        {
+            wave.end_tick = Some(tick);
+            wave.outcome = outcome;
+        }
+    }
+
+    /// Update the stats of a player both for the current wave and the totals
+    fn player_stats<F: Fn(&mut MvmPlayerStats)>(
+        &mut self,
+        players: &PlayerRegistry,
+        entity: u16,
+        update: F,
+    ) {
+        if let Some(user_id) = players.user_id(EntityId::from(entity as u32)) {
+            update(self.state.players.entry(user_id).or_default());
+            if let Some(wave) = self.state.waves.last_mut() {
+                update(wave.players.entry(user_id).or_default());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_mvm_waves() {
+    use crate::demo::gameevent_gen::{
+        MvmBeginWaveEvent, MvmBombResetByPlayerEvent, MvmPickupCurrencyEvent, MvmWaveCompleteEvent,
+        MvmWaveFailedEvent,
+    };
+    use crate::demo::parser::test_util::{event_message as event, *};
+    use crate::demo::sendprop::SendPropValue;
+
+    let user = UserId::from(3u16);
+    let mut parser_state = parser_state();
+    add_user(&mut parser_state, 0, 3);
+
+    let mut analyser = MvmAnalyser::new();
+    analyser.handle_data_tables(
+        &[],
+        &server_classes(&["CTFObjectiveResource"]),
+        &parser_state,
+    );
+
+    let world_money = |money: i64| {
+        entities_message(vec![packet_entity(
+            0,
+            30,
+            vec![(
+                "DT_TFObjectiveResource",
+                "m_nMvMWorldMoney",
+                SendPropValue::Integer(money),
+            )],
+        )])
+    };
+    let begin = || {
+        event(GameEvent::MvmBeginWave(MvmBeginWaveEvent {
+            wave_index: 0,
+            max_waves: 7,
+            advanced: 0,
+        }))
+    };
+    let pickup = |currency| {
+        event(GameEvent::MvmPickupCurrency(MvmPickupCurrencyEvent {
+            player: 1,
+            currency,
+        }))
+    };
+
+    let messages = [
+        (100, begin()),
+        (110, world_money(100)),
+        // a pack of 50 is dropped while a pack of 40 is collected
+        (120, pickup(40)),
+        (120, world_money(110)),
+        // the pickup event can follow the entity update
+        (130, world_money(80)),
+        (130, pickup(30)),
+        (
+            140,
+            event(GameEvent::MvmBombResetByPlayer(MvmBombResetByPlayerEvent {
+                player: 1,
+            })),
+        ),
+        (500, event(GameEvent::MvmWaveFailed(MvmWaveFailedEvent {}))),
+        (600, begin()),
+        // the money left on the ground disappears
+        (600, world_money(0)),
+        (610, world_money(25)),
+        (620, pickup(25)),
+        (620, world_money(0)),
+        (
+            900,
+            event(GameEvent::MvmWaveComplete(MvmWaveCompleteEvent {
+                advanced: false,
+            })),
+        ),
+    ];
+    for (tick, message) in messages {
+        analyser.handle_message(&message, DemoTick::from(tick), &parser_state);
+    }
+    let state = analyser.into_output(&parser_state);
+
+    assert_eq!(2, state.waves.len());
+    assert_eq!(1, state.waves[0].index);
+    assert_eq!(WaveOutcome::Failed, state.waves[0].outcome);
+    assert_eq!(150, state.waves[0].credits_dropped);
+    assert_eq!(70, state.waves[0].credits_collected);
+    assert_eq!(80, state.waves[0].credits_missed);
+    assert_eq!(1, state.waves[0].bomb_resets);
+    assert_eq!(WaveOutcome::Completed, state.waves[1].outcome);
+    assert_eq!(Some(DemoTick::from(900)), state.waves[1].end_tick);
+    assert_eq!(25, state.waves[1].credits_dropped);
+    assert_eq!(0, state.waves[1].credits_missed);
+    assert_eq!(25, state.waves[1].players[&user].credits_collected);
+    assert_eq!(95, state.players[&user].credits_collected);
+    assert_eq!(1, state.players[&user].bomb_resets);
+}
@@ -0,0 +1,655 @@
+use crate::demo::data::DemoTick;
+use crate::demo::gameevent_gen::{
+    ControlPointEndTouchEvent, ControlPointStartTouchEvent, TeamPlayCaptureBlockedEvent,
+    TeamPlayPointCapturedEvent, TeamPlayPointStartCaptureEvent,
+};
+use crate::demo::gamevent::GameEvent;
+use crate::demo::message::gameevent::GameEventMessage;
+use crate::demo::message::packetentities::{EntityId, PacketEntity};
+use crate::demo::message::{Message, MessageType};
+use crate::demo::packet::datatable::{ParseSendTable, ServerClass, ServerClassName};
+use crate::demo::parser::analyser::{Team, UserId};
+use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
+use crate::demo::parser::registry::PlayerRegistry;
+use crate::ParserState;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// A control point changing owner, the first change for every point is the owner at the start of the demo
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OwnerChange {
+    pub tick: DemoTick,
+    pub point: u8,
+    /// `Team::Other` for a neutral point
+    pub owner: Team,
+}
+
+/// A control point being captured
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Capture {
+    pub tick: DemoTick,
+    pub point: u8,
+    pub point_name: String,
+    pub team: Team,
+    pub cappers: Vec<UserId>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureOutcome {
+    Captured,
+    Broken,
+    /// The demo ended while the capture was in progress
+    #[default]
+    Unfinished,
+}
+
+/// A team trying to capture a point, from the start of the capture until it's either captured or broken
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CaptureAttempt {
+    pub point: u8,
+    pub point_name: String,
+    pub team: Team,
+    pub start_tick: DemoTick,
+    pub end_tick: DemoTick,
+    pub outcome: CaptureOutcome,
+    /// The players that started the capture
+    pub cappers: Vec<UserId>,
+    /// The number of ticks every player spend on the point during the attempt, including defending players
+    pub time_on_point: BTreeMap<UserId, u32>,
+}
+
+/// A capture being blocked
+///
+/// Blocks by killing a capping player count as a defense and have the killed player as `victim`,
+/// blocks by standing on the point have no victim.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Block {
+    pub tick: DemoTick,
+    pub point: u8,
+    pub point_name: String,
+    pub blocker: UserId,
+    pub victim: Option<UserId>,
+}
+
+/// The time in seconds each team owned the control points while a round was in progress
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct HoldTime {
+    pub red: f32,
+    pub blue: f32,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ObjectiveState {
+    pub owners: Vec<OwnerChange>,
+    pub captures: Vec<Capture>,
+    pub attempts: Vec<CaptureAttempt>,
+    pub blocks: Vec<Block>,
+    /// Only set for KOTH maps
+    pub koth_hold_time: Option<HoldTime>,
+    pub interval_per_tick: f32,
+}
+
+impl ObjectiveState {
+    /// The owner of a point at the given tick, `None` if the owner isn't known at that tick
+    pub fn owner_at(&self, point: u8, tick: DemoTick) -> Option<Team> {
+        self.owners
+            .iter()
+            .rev()
+            .find(|change| change.point == point && change.tick <= tick)
+            .map(|change| change.owner)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PointOwner {
+    owner: Team,
+    since: DemoTick,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct ObjectiveAnalyser {
+    state: ObjectiveState,
+    tick: DemoTick,
+    /// Whether the map name has the `koth_` prefix, only used if the game rules don't tell the game mode
+    koth_map: bool,
+    /// The game mode from the `m_bPlayingKoth` game rules prop
+    playing_koth: Option<bool>,
+    point_count: u8,
+    round_over: bool,
+    hold_ticks: [u32; 2],
+    points: BTreeMap<u8, PointOwner>,
+    /// The capture attempt in progress for every point, as index in `state.attempts`
+    active_attempts: HashMap<u8, usize>,
+    /// The players touching every point, with the tick they started touching
+    touching: HashMap<u8, HashMap<UserId, DemoTick>>,
+    class_names: Vec<ServerClassName>, // indexed by ClassId
+}
+
+impl MessageHandler for ObjectiveAnalyser {
+    type Output = ObjectiveState;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        matches!(
+            message_type,
+            MessageType::PacketEntities | MessageType::GameEvent | MessageType::ServerInfo
+        )
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, parser_state: &ParserState) {
+        self.tick = tick;
+        match message {
+            Message::ServerInfo(message) => {
+                self.state.interval_per_tick = message.interval_per_tick;
+                self.koth_map = message.map.starts_with("koth_");
+            }
+            Message::PacketEntities(message) => {
+                for entity in &message.entities {
+                    self.handle_entity(entity, parser_state);
+                }
+            }
+            Message::GameEvent(GameEventMessage { event, .. }) => match event {
+                GameEvent::TeamPlayPointStartCapture(event) => {
+                    self.handle_start_capture(event, &parser_state.players)
+                }
+                GameEvent::TeamPlayPointCaptured(event) => {
+                    self.handle_captured(event, &parser_state.players)
+                }
+                GameEvent::TeamPlayCaptureBroken(event) => {
+                    self.end_attempt(event.cp, CaptureOutcome::Broken)
+                }
+                GameEvent::TeamPlayCaptureBlocked(event) => {
+                    self.handle_blocked(event, &parser_state.players)
+                }
+                GameEvent::ControlPointStartTouch(event) => {
+                    self.handle_start_touch(event, &parser_state.players)
+                }
+                GameEvent::ControlPointEndTouch(event) => {
+                    self.handle_end_touch(event, &parser_state.players)
+                }
+                GameEvent::TeamPlayRoundStart(_) => self.handle_round_start(),
+                GameEvent::TeamPlayRoundWin(_) => self.handle_round_end(),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn handle_data_tables(
+        &mut self,
+        _parse_tables: &[ParseSendTable],
+        server_classes: &[ServerClass],
+        _parser_state: &ParserState,
+    ) {
+        self.class_names = server_classes
+            .iter()
+            .map(|class| &class.name)
+            .cloned()
+            .collect();
+    }
+
+    fn into_output(mut self, _state: &ParserState) -> Self::Output {
+        self.finish();
+        self.state
+    }
+}
+
+impl BorrowMessageHandler for ObjectiveAnalyser {
+    type BorrowedOutput<'a> = &'a Self::Output;
+
+    fn borrow_output<'a>(&'a self, _state: &ParserState) -> Self::BorrowedOutput<'a> {
+        &self.state
+    }
+}
+
+impl ObjectiveAnalyser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn handle_entity(&mut self, entity: &PacketEntity, parser_state: &ParserState) {
+        let class_name: &str = self
+            .class_names
+            .get(usize::from(entity.server_class))
+            .map(|class_name| class_name.as_str())
+            .unwrap_or("");
+        if class_name == "CTFGameRulesProxy" {
+            if let Some(prop) =
+                entity.get_prop_by_name("DT_TFGameRules", "m_bPlayingKoth", parser_state)
+            {
+                self.playing_koth = Some(i64::try_from(&prop.value).unwrap_or_default() != 0);
+            }
+        } else if class_name == "CTFObjectiveResource" {
+            if let Some(prop) = entity.get_prop_by_name(
+                "DT_BaseTeamObjectiveResource",
+                "m_iNumControlPoints",
+                parser_state,
+            ) {
+                self.point_count = i64::try_from(&prop.value).unwrap_or_default() as u8;
+            }
+
+            for prop in entity.props(parser_state) {
+                if let Some((table_name, prop_name)) = prop.identifier.names() {
+                    if table_name.as_str() == "m_iOwner" {
+                        match u8::from_str(prop_name.as_str()) {
+                            // the owner array has room for more points than the map uses
+                            Ok(point) if point < self.point_count => {
+                                let owner =
+                                    Team::new(i64::try_from(&prop.value).unwrap_or_default());
+                                self.set_owner(point, owner);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_owner(&mut self, point: u8, owner: Team) {
+        if self.points.get(&point).map(|current| current.owner) == Some(owner) {
+            return;
+        }
+        self.accrue_hold_time(point);
+        self.points.insert(
+            point,
+            PointOwner {
+                owner,
+                since: self.tick,
+            },
+        );
+        self.state.owners.push(OwnerChange {
+            tick: self.tick,
+            point,
+            owner,
+        });
+    }
+
+    /// Add the time since the last update to the hold time of the owning team
+    fn accrue_hold_time(&mut self, point: u8) {
+        let tick = self.tick;
+        let round_over = self.round_over;
+        if let Some(current) = self.points.get_mut(&point) {
+            if !round_over {
+                let ticks = u32::from(tick).saturating_sub(u32::from(current.since));
+                match current.owner {
+                    Team::Red => self.hold_ticks[0] += ticks,
+                    Team::Blue => self.hold_ticks[1] += ticks,
+                    _ => {}
+                }
+            }
+            current.since = tick;
+        }
+    }
+
+    fn accrue_all_hold_time(&mut self) {
+        let points: Vec<u8> = self.points.keys().copied().collect();
+        for point in points {
+            self.accrue_hold_time(point);
+        }
+    }
+
+    fn handle_round_start(&mut self) {
+        self.accrue_all_hold_time();
+        self.round_over = false;
+    }
+
+    fn handle_round_end(&mut self) {
+        self.accrue_all_hold_time();
+        self.round_over = true;
+    }
+
+    fn handle_start_capture(
+        &mut self,
+        event: &TeamPlayPointStartCaptureEvent,
+        players: &PlayerRegistry,
+    ) {
+        self.end_attempt(event.cp, CaptureOutcome::Broken);
+
+        let start_tick = self.tick;
+        // players that were already on the point when the attempt started count from the start of the attempt
+        let time_on_point = self
+            .touching
+            .get(&event.cp)
+            .map(|players| players.keys().map(|user_id| (*user_id, 0)).collect())
+            .unwrap_or_default();
+        self.active_attempts
+            .insert(event.cp, self.state.attempts.len());
+        self.state.attempts.push(CaptureAttempt {
+            point: event.cp,
+            point_name: event.cp_name.to_string(),
+            team: Team::new(event.cap_team),
+            start_tick,
+            end_tick: start_tick,
+            outcome: CaptureOutcome::Unfinished,
+            cappers: players.cappers(event.cappers.as_bytes()),
+            time_on_point,
+        });
+    }
+
+    fn handle_captured(&mut self, event: &TeamPlayPointCapturedEvent, players: &PlayerRegistry) {
+        let team = Team::new(event.team);
+        self.state.captures.push(Capture {
+            tick: self.tick,
+            point: event.cp,
+            point_name: event.cp_name.to_string(),
+            team,
+            cappers: players.cappers(event.cappers.as_bytes()),
+        });
+        self.end_attempt(event.cp, CaptureOutcome::Captured);
+        self.set_owner(event.cp, team);
+    }
+
+    fn handle_blocked(&mut self, event: &TeamPlayCaptureBlockedEvent, players: &PlayerRegistry) {
+        if let Some(blocker) = players.user_id(EntityId::from(event.blocker as u32)) {
+            self.state.blocks.push(Block {
+                tick: self.tick,
+                point: event.cp,
+                point_name: event.cp_name.to_string(),
+                blocker,
+                victim: players.user_id(EntityId::from(event.victim as u32)),
+            });
+        }
+    }
+
+    fn handle_start_touch(
+        &mut self,
+        event: &ControlPointStartTouchEvent,
+        players: &PlayerRegistry,
+    ) {
+        if let Some(user_id) = players.user_id(EntityId::from(event.player as u32)) {
+            self.touching
+                .entry(event.area as u8)
+                .or_default()
+                .insert(user_id, self.tick);
+        }
+    }
+
+    fn handle_end_touch(&mut self, event: &ControlPointEndTouchEvent, players: &PlayerRegistry) {
+        let point = event.area as u8;
+        if let Some(user_id) = players.user_id(EntityId::from(event.player as u32)) {
+            if let Some(start) = self
+                .touching
+                .get_mut(&point)
+                .and_then(|players| players.remove(&user_id))
+            {
+                self.add_time_on_point(point, user_id, start);
+            }
+        }
+    }
+
+    /// Add the time a player spend touching a point to the active capture attempt for the point
+    fn add_time_on_point(&mut self, point: u8, user_id: UserId, touch_start: DemoTick) {
+        let tick = self.tick;
+        if let Some(attempt) = self
+            .active_attempts
+            .get(&point)
+            .and_then(|index| self.state.attempts.get_mut(*index))
+        {
+            let start = u32::from(touch_start).max(u32::from(attempt.start_tick));
+            *attempt.time_on_point.entry(user_id).or_default() +=
+                u32::from(tick).saturating_sub(start);
+        }
+    }
+
+    fn end_attempt(&mut self, point: u8, outcome: CaptureOutcome) {
+        let touching: Vec<(UserId, DemoTick)> = self
+            .touching
+            .get(&point)
+            .map(|players| players.iter().map(|(user, tick)| (*user, *tick)).collect())
+            .unwrap_or_default();
+        for (user_id, touch_start) in touching {
+            self.add_time_on_point(point, user_id, touch_start);
+        }
+
+        if let Some(index) = self.active_attempts.remove(&point) {
+            let attempt = &mut self.state.attempts[index];
+            attempt.end_tick = self.tick;
+            attempt.outcome = outcome;
+        }
+    }
+
+    fn finish(&mut self) {
+        let points: Vec<u8> = self.active_attempts.keys().copied().collect();
+        for point in points {
+            self.end_attempt(point, CaptureOutcome::Unfinished);
+        }
+        self.accrue_all_hold_time();
+
+        if self.playing_koth.unwrap_or(self.koth_map) {
+            let interval_per_tick = self.state.interval_per_tick;
+            self.state.koth_hold_time = Some(HoldTime {
+                red: self.hold_ticks[0] as f32 * interval_per_tick,
+                blue: self.hold_ticks[1] as f32 * interval_per_tick,
+            });
+        }
+    }
+}
+
+#[test]
+fn test_capture_attempts() {
+    use crate::demo::gameevent_gen::TeamPlayCaptureBrokenEvent;
+    use crate::demo::parser::test_util::{event_message as event, *};
+
+    let red = UserId::from(10u16);
+    let blue = UserId::from(20u16);
+    let mut parser_state = parser_state();
+    add_user(&mut parser_state, 0, 10);
+    add_user(&mut parser_state, 1, 20);
+
+    let mut analyser = ObjectiveAnalyser::new();
+    analyser.handle_data_tables(
+        &[],
+        &server_classes(&["CTFObjectiveResource"]),
+        &parser_state,
+    );
+
+    let start_touch = |player: u16| {
+        event(GameEvent::ControlPointStartTouch(
+            ControlPointStartTouchEvent { player, area: 0 },
+        ))
+    };
+    let start_capture = |cp: u8, cap_team: u8, cappers: &str| {
+        event(GameEvent::TeamPlayPointStartCapture(
+            TeamPlayPointStartCaptureEvent {
+                cp,
+                cp_name: "point".into(),
+                team: 0,
+                cap_team,
+                cappers: cappers.into(),
+                cap_time: 2.0,
+            },
+        ))
+    };
+
+    let messages = [
+        (1, server_info_message("cp_process_final")),
+        (100, start_touch(1)),
+        (110, start_capture(0, 2, "\u{1}")),
+        (130, start_touch(2)),
+        (
+            140,
+            event(GameEvent::TeamPlayCaptureBlocked(
+                TeamPlayCaptureBlockedEvent {
+                    cp: 0,
+                    cp_name: "point".into(),
+                    blocker: 2,
+                    victim: 1,
+                },
+            )),
+        ),
+        (
+            140,
+            event(GameEvent::ControlPointEndTouch(ControlPointEndTouchEvent {
+                player: 1,
+                area: 0,
+            })),
+        ),
+        (
+            150,
+            event(GameEvent::TeamPlayCaptureBroken(
+                TeamPlayCaptureBrokenEvent {
+                    cp: 0,
+                    cp_name: "point".into(),
+                    time_remaining: 1.0,
+                },
+            )),
+        ),
+        (200, start_touch(1)),
+        (200, start_capture(0, 2, "\u{1}")),
+        (
+            260,
+            event(GameEvent::TeamPlayPointCaptured(
+                TeamPlayPointCapturedEvent {
+                    cp: 0,
+                    cp_name: "point".into(),
+                    team: 2,
+                    cappers: "\u{1}".into(),
+                },
+            )),
+        ),
+        (300, start_capture(1, 3, "\u{2}")),
+    ];
+    for (tick, message) in messages {
+        analyser.handle_message(&message, DemoTick::from(tick), &parser_state);
+    }
+    let state = analyser.into_output(&parser_state);
+
+    assert_eq!(
+        vec![Capture {
+            tick: DemoTick::from(260),
+            point: 0,
+            point_name: "point".into(),
+            team: Team::Red,
+            cappers: vec![red],
+        }],
+        state.captures
+    );
+    assert_eq!(
+        vec![Block {
+            tick: DemoTick::from(140),
+            point: 0,
+            point_name: "point".into(),
+            blocker: blue,
+            victim: Some(red),
+        }],
+        state.blocks
+    );
+
+    assert_eq!(3, state.attempts.len());
+    // the blocking player is counted from the moment they stepped on the point
+    assert_eq!(CaptureOutcome::Broken, state.attempts[0].outcome);
+    assert_eq!(DemoTick::from(110), state.attempts[0].start_tick);
+    assert_eq!(DemoTick::from(150), state.attempts[0].end_tick);
+    assert_eq!(vec![red], state.attempts[0].cappers);
+    assert_eq!(
+        BTreeMap::from([(red, 30), (blue, 20)]),
+        state.attempts[0].time_on_point
+    );
+    // the player still on the point from the last attempt is counted from the start of the attempt
+    assert_eq!(CaptureOutcome::Captured, state.attempts[1].outcome);
+    assert_eq!(DemoTick::from(260), state.attempts[1].end_tick);
+    assert_eq!(
+        BTreeMap::from([(red, 60), (blue, 60)]),
+        state.attempts[1].time_on_point
+    );
+    assert_eq!(CaptureOutcome::Unfinished, state.attempts[2].outcome);
+    assert_eq!(Team::Blue, state.attempts[2].team);
+    assert_eq!(vec![blue], state.attempts[2].cappers);
+
+    assert_eq!(None, state.owner_at(0, DemoTick::from(259)));
+    assert_eq!(Some(Team::Red), state.owner_at(0, DemoTick::from(260)));
+    assert_eq!(None, state.koth_hold_time);
+}
+
+#[cfg(test)]
+fn koth_hold_time(map: &str, playing_koth: Option<bool>) -> Option<HoldTime> {
+    use crate::demo::gameevent_gen::{TeamPlayRoundStartEvent, TeamPlayRoundWinEvent};
+    use crate::demo::parser::test_util::{event_message as event, *};
+    use crate::demo::sendprop::SendPropValue;
+
+    let parser_state = parser_state();
+    let mut analyser = ObjectiveAnalyser::new();
+    analyser.handle_data_tables(
+        &[],
+        &server_classes(&["CTFObjectiveResource", "CTFGameRulesProxy"]),
+        &parser_state,
+    );
+
+    let owner = |team: Team| {
+        entities_message(vec![packet_entity(
+            0,
+            30,
+            vec![
+                (
+                    "DT_BaseTeamObjectiveResource",
+                    "m_iNumControlPoints",
+                    SendPropValue::Integer(1),
+                ),
+                ("m_iOwner", "000", SendPropValue::Integer(team as i64)),
+            ],
+        )])
+    };
+    let round_win = |team: u8| {
+        event(GameEvent::TeamPlayRoundWin(TeamPlayRoundWinEvent {
+            team,
+            win_reason: 1,
+            flag_cap_limit: 0,
+            full_round: 1,
+            round_time: 0.0,
+            losing_team_num_caps: 0,
+            was_sudden_death: 0,
+        }))
+    };
+    let round_start = || {
+        event(GameEvent::TeamPlayRoundStart(TeamPlayRoundStartEvent {
+            full_reset: true,
+        }))
+    };
+
+    let mut messages = vec![(1, server_info_message(map))];
+    if let Some(playing_koth) = playing_koth {
+        messages.push((
+            1,
+            entities_message(vec![packet_entity(
+                1,
+                20,
+                vec![(
+                    "DT_TFGameRules",
+                    "m_bPlayingKoth",
+                    SendPropValue::Integer(playing_koth as i64),
+                )],
+            )]),
+        ));
+    }
+    messages.extend([
+        (10, owner(Team::Other)),
+        (10, round_start()),
+        (100, owner(Team::Red)),
+        (400, owner(Team::Blue)),
+        (500, round_win(3)),
+        // the time between rounds isn't counted
+        (600, round_start()),
+        (700, owner(Team::Red)),
+        (800, owner(Team::Red)),
+    ]);
+    for (tick, message) in messages {
+        analyser.handle_message(&message, DemoTick::from(tick), &parser_state);
+    }
+    analyser.into_output(&parser_state).koth_hold_time
+}
+
+#[test]
+fn test_koth_hold_time() {
+    let expected = Some(HoldTime {
+        red: 400.0 * 0.015,
+        blue: 200.0 * 0.015,
+    });
+    assert_eq!(expected, koth_hold_time("koth_product_final", None));
+    assert_eq!(None, koth_hold_time("cp_process_final", None));
+    // the game rules take precedence over the map name
+    assert_eq!(expected, koth_hold_time("workshop_product", Some(true)));
+    assert_eq!(None, koth_hold_time("koth_ctf_mashup", Some(false)));
+}
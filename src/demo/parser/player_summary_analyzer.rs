@@ -1,13 +1,16 @@
 use crate::demo::data::DemoTick;
+use crate::demo::gamevent::GameEvent;
 use crate::demo::message::packetentities::EntityId;
 use crate::demo::message::packetentities::PacketEntity;
 use crate::demo::message::{Message, MessageType};
 use crate::demo::packet::datatable::ClassId;
+use crate::demo::packet::message::MessagePacketMeta;
 use crate::demo::packet::stringtable::StringTableEntry;
-use crate::demo::parser::analyser::UserInfo;
+use crate::demo::parser::analyser::{Team, UserInfo};
 use crate::demo::parser::gamestateanalyser::UserId;
 use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
-use crate::{ParserState, ReadResult, Stream};
+use crate::demo::sendprop::SendPropValue;
+use crate::ParserState;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 
@@ -17,13 +20,37 @@ use std::collections::{BTreeMap, HashMap};
  * scoreboard for every player if they took a snapshot at the time the demo finishes (such as the end
  * of a match or round).
  */
-#[derive(Default, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PlayerSummaryAnalyzer {
     state: PlayerSummaryState,
-    user_id_map: HashMap<EntityId, UserId>,
+    tick: DemoTick,
+    interval_per_tick: f32,
+    /// The start of the current round, if a round is in progress
+    round_start: Option<DemoTick>,
+    /// The tick and winner of a won round that hasn't been finished yet
+    round_win: Option<(DemoTick, Team)>,
+    last_round_end: DemoTick,
+    /// The match totals at the end of the last round
+    round_baseline: HashMap<UserId, PlayerSummary>,
+    /// The connection state and team of every player resource slot, by entity id
+    resource_slots: HashMap<u32, ResourceSlot>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+struct ResourceSlot {
+    connected: bool,
+    team: Team,
+}
+
+/// The scoreboard stats of a player
+///
+/// The scoring data fields (everything except `scoreboard`) cover the entire match, since the match-scoped copy of
+/// `DT_TFPlayerScoringDataExclusive` is never lower than the round-scoped copy, the highest value of both copies is used.
+/// Per round stats can be found in the round summaries of the [`PlayerSummaryState`], which add up to the match totals.
+///
+/// `ubercharges` is read from `m_iInvulns` and `support` is the sum of the healing and damage assists
+/// from the player resource.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct PlayerSummary {
     pub points: u32,
     pub kills: u32,
@@ -42,19 +69,131 @@ pub struct PlayerSummary {
     pub bonus_points: u32,
     pub support: u32,
     pub damage_dealt: u32,
+    #[serde(default)]
+    pub suicides: u32,
+    #[serde(default)]
+    pub buildings_built: u32,
+    #[serde(default)]
+    pub crits: u32,
+    #[serde(default)]
+    pub resupply_points: u32,
+    #[serde(default)]
+    pub scoreboard: ScoreboardStats,
+}
+
+/// The per-player values from the `CTFPlayerResource` arrays
+///
+/// `ping` and `max_buffed_health` are the last networked value, all other values cover the entire match.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ScoreboardStats {
+    /// The engine score (`m_iScore`)
+    pub score: u32,
+    /// The score shown on the scoreboard (`m_iTotalScore`)
+    pub total_score: u32,
+    pub ping: u32,
+    pub damage: u32,
+    pub healing: u32,
+    pub healing_assist: u32,
+    pub damage_assist: u32,
+    pub max_buffed_health: u32,
+}
+
+impl ScoreboardStats {
+    /// The stats gained since an earlier value, `ping` and `max_buffed_health` are kept as is
+    pub fn since(&self, earlier: &ScoreboardStats) -> ScoreboardStats {
+        ScoreboardStats {
+            score: self.score.saturating_sub(earlier.score),
+            total_score: self.total_score.saturating_sub(earlier.total_score),
+            ping: self.ping,
+            damage: self.damage.saturating_sub(earlier.damage),
+            healing: self.healing.saturating_sub(earlier.healing),
+            healing_assist: self.healing_assist.saturating_sub(earlier.healing_assist),
+            damage_assist: self.damage_assist.saturating_sub(earlier.damage_assist),
+            max_buffed_health: self.max_buffed_health,
+        }
+    }
+}
+
+impl PlayerSummary {
+    /// The stats gained since an earlier summary of the same player
+    pub fn since(&self, earlier: &PlayerSummary) -> PlayerSummary {
+        PlayerSummary {
+            points: self.points.saturating_sub(earlier.points),
+            kills: self.kills.saturating_sub(earlier.kills),
+            assists: self.assists.saturating_sub(earlier.assists),
+            deaths: self.deaths.saturating_sub(earlier.deaths),
+            buildings_destroyed: self
+                .buildings_destroyed
+                .saturating_sub(earlier.buildings_destroyed),
+            captures: self.captures.saturating_sub(earlier.captures),
+            defenses: self.defenses.saturating_sub(earlier.defenses),
+            dominations: self.dominations.saturating_sub(earlier.dominations),
+            revenges: self.revenges.saturating_sub(earlier.revenges),
+            ubercharges: self.ubercharges.saturating_sub(earlier.ubercharges),
+            headshots: self.headshots.saturating_sub(earlier.headshots),
+            teleports: self.teleports.saturating_sub(earlier.teleports),
+            healing: self.healing.saturating_sub(earlier.healing),
+            backstabs: self.backstabs.saturating_sub(earlier.backstabs),
+            bonus_points: self.bonus_points.saturating_sub(earlier.bonus_points),
+            support: self.support.saturating_sub(earlier.support),
+            damage_dealt: self.damage_dealt.saturating_sub(earlier.damage_dealt),
+            suicides: self.suicides.saturating_sub(earlier.suicides),
+            buildings_built: self.buildings_built.saturating_sub(earlier.buildings_built),
+            crits: self.crits.saturating_sub(earlier.crits),
+            resupply_points: self.resupply_points.saturating_sub(earlier.resupply_points),
+            scoreboard: self.scoreboard.since(&earlier.scoreboard),
+        }
+    }
+
+    /// Whether the player gained any stats, ignoring `ping` and `max_buffed_health` which aren't cumulative
+    pub fn has_activity(&self) -> bool {
+        let cumulative = PlayerSummary {
+            scoreboard: ScoreboardStats {
+                ping: 0,
+                max_buffed_health: 0,
+                ..self.scoreboard.clone()
+            },
+            ..self.clone()
+        };
+        cumulative != PlayerSummary::default()
+    }
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, PartialEq)]
+/// The player summaries for a single round
+///
+/// All stats are the difference between the match totals at the end of the round and at the end of the previous round,
+/// so the rounds add up to the match totals, only `ping` and `max_buffed_health` are the last networked value.
+/// A won round is only finished when the next round starts, so stats gained after the win count towards the won round,
+/// the round that is in progress when the demo ends is included as well.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct RoundSummary {
+    pub start_tick: DemoTick,
+    pub end_tick: DemoTick,
+    /// The length of the round in seconds
+    pub duration: f32,
+    /// The winning team, `None` if the round didn't finish within the demo
+    pub winner: Option<Team>,
+    pub player_summaries: HashMap<UserId, PlayerSummary>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PlayerSummaryState {
+    /// The match totals for every player
     pub player_summaries: HashMap<UserId, PlayerSummary>,
     pub users: BTreeMap<UserId, UserInfo>,
+    /// The summaries of every round
+    #[serde(default)]
+    pub rounds: Vec<RoundSummary>,
 }
 
 impl MessageHandler for PlayerSummaryAnalyzer {
     type Output = PlayerSummaryState;
 
     fn does_handle(message_type: MessageType) -> bool {
-        matches!(message_type, MessageType::PacketEntities)
+        matches!(
+            message_type,
+            MessageType::PacketEntities | MessageType::GameEvent | MessageType::ServerInfo
+        )
     }
 
     fn handle_message(&mut self, message: &Message, _tick: DemoTick, parser_state: &ParserState) {
@@ -64,11 +203,42 @@ impl MessageHandler for PlayerSummaryAnalyzer {
                     self.handle_packet_entity(&entity, parser_state);
                 }
             }
+            Message::ServerInfo(message) => self.interval_per_tick = message.interval_per_tick,
+            Message::GameEvent(message) => match &message.event {
+                GameEvent::TeamPlayRoundStart(_) => {
+                    if self.round_start.is_some() || self.round_win.is_some() {
+                        self.end_round();
+                    }
+                    self.round_start = Some(self.tick);
+                }
+                GameEvent::TeamPlayRoundWin(event) => {
+                    if self.round_win.is_some() {
+                        self.end_round();
+                    }
+                    self.round_win = Some((self.tick, Team::new(event.team)));
+                }
+                _ => {}
+            },
             _ => {}
         }
     }
 
-    fn into_output(self, _parser_state: &ParserState) -> <Self as MessageHandler>::Output {
+    fn handle_packet_meta(
+        &mut self,
+        tick: DemoTick,
+        _meta: &MessagePacketMeta,
+        _parser_state: &ParserState,
+    ) {
+        self.tick = tick;
+    }
+
+    fn into_output(mut self, _parser_state: &ParserState) -> <Self as MessageHandler>::Output {
+        if self.round_start.is_some()
+            || self.round_win.is_some()
+            || self.state.player_summaries != self.round_baseline
+        {
+            self.end_round();
+        }
         self.state
     }
 
@@ -76,21 +246,27 @@ impl MessageHandler for PlayerSummaryAnalyzer {
         &mut self,
         table: &str,
         index: usize,
-        entry: &StringTableEntry,
-        _parser_state: &ParserState,
+        _entry: &StringTableEntry,
+        parser_state: &ParserState,
     ) {
         if table == "userinfo" {
-            let _ = self.parse_user_info(
-                index,
-                entry.text.as_ref().map(|s| s.as_ref()),
-                entry.extra_data.as_ref().map(|data| data.data.clone()),
-            );
+            if let Some(player) = parser_state.players.get_by_table_index(index) {
+                self.state
+                    .users
+                    .entry(player.user_id)
+                    .and_modify(|info| {
+                        info.entity_id = player.entity_id;
+                    })
+                    .or_insert_with(|| player.into());
+            }
         }
     }
 }
 
 impl BorrowMessageHandler for PlayerSummaryAnalyzer {
-    fn borrow_output(&self, _state: &ParserState) -> &Self::Output {
+    type BorrowedOutput<'a> = &'a Self::Output;
+
+    fn borrow_output<'a>(&'a self, _state: &ParserState) -> Self::BorrowedOutput<'a> {
         &self.state
     }
 }
@@ -109,12 +285,10 @@ fn parse_integer_prop<F>(
 ) where
     F: FnOnce(u32),
 {
-    use crate::demo::sendprop::SendPropValue;
-
     match packet.get_prop_by_name(table, name, parser_state) {
         Some(prop) => {
             match prop.value {
-                SendPropValue::Integer(val) => handler(val as u32),
+                SendPropValue::Integer(_) => handler(prop_value(&prop.value)),
                 _ => {} // not an integer, ignore
             }
         }
@@ -122,14 +296,52 @@ fn parse_integer_prop<F>(
     }
 }
 
+fn prop_value(value: &SendPropValue) -> u32 {
+    i64::try_from(value)
+        .unwrap_or_default()
+        .clamp(0, u32::MAX as i64) as u32
+}
+
 impl PlayerSummaryAnalyzer {
     pub fn new() -> Self {
         Self::default()
     }
 
-    fn handle_packet_entity(&mut self, packet: &PacketEntity, parser_state: &ParserState) {
-        use crate::demo::sendprop::SendPropValue;
+    /// Finish the current round with the stats gained since the end of the previous round
+    fn end_round(&mut self) {
+        let start_tick = self.round_start.take().unwrap_or(self.last_round_end);
+        let (end_tick, winner) = match self.round_win.take() {
+            Some((tick, winner)) => (tick, Some(winner)),
+            None => (self.tick, None),
+        };
+        let player_summaries = self
+            .state
+            .player_summaries
+            .iter()
+            .map(|(user_id, summary)| {
+                let baseline = self
+                    .round_baseline
+                    .get(user_id)
+                    .cloned()
+                    .unwrap_or_default();
+                (*user_id, summary.since(&baseline))
+            })
+            .filter(|(_, summary)| summary.has_activity())
+            .collect();
+
+        self.state.rounds.push(RoundSummary {
+            start_tick,
+            end_tick,
+            duration: u32::from(end_tick).saturating_sub(start_tick.into()) as f32
+                * self.interval_per_tick,
+            winner,
+            player_summaries,
+        });
+        self.round_baseline = self.state.player_summaries.clone();
+        self.last_round_end = self.tick;
+    }
 
+    fn handle_packet_entity(&mut self, packet: &PacketEntity, parser_state: &ParserState) {
         // println!("Known server classes: {:?}", parser_state.server_classes);
 
         if let Some(class) = parser_state
@@ -139,10 +351,10 @@ impl PlayerSummaryAnalyzer {
             // println!("Got a {} data packet: {:?}", class.name, packet);
             match class.name.as_str() {
                 "CTFPlayer" => {
-                    match self.user_id_map.get(&packet.entity_index) {
+                    match parser_state.players.user_id(packet.entity_index) {
                         Some(user_id) => {
                             let summaries = &mut self.state.player_summaries;
-                            let player_summary = summaries.entry(*user_id).or_default();
+                            let player_summary = summaries.entry(user_id).or_default();
 
                             // Extract scoreboard information, if present, and update the player's summary accordingly
                             // NOTE: Multiple DT_TFPlayerScoringDataExclusive structures may be present - one for the entire match,
@@ -173,7 +385,7 @@ impl PlayerSummaryAnalyzer {
                              * Member: m_iPoints (offset 80) (type integer) (bits 10) (Unsigned)
                              *
                              * NOTE: support points aren't included here, but is equal to the sum of m_iHealingAssist and m_iDamageAssist
-                             * TODO: pull data for support points
+                             *       from the CTFPlayerResource
                              */
                             parse_integer_prop(
                                 packet,
@@ -221,7 +433,17 @@ impl PlayerSummaryAnalyzer {
                                     }
                                 },
                             );
-                            // ignore m_iSuicides
+                            parse_integer_prop(
+                                packet,
+                                "DT_TFPlayerScoringDataExclusive",
+                                "m_iSuicides",
+                                parser_state,
+                                |suicides| {
+                                    if suicides > player_summary.suicides {
+                                        player_summary.suicides = suicides;
+                                    }
+                                },
+                            );
                             parse_integer_prop(
                                 packet,
                                 "DT_TFPlayerScoringDataExclusive",
@@ -244,7 +466,17 @@ impl PlayerSummaryAnalyzer {
                                     }
                                 },
                             );
-                            // ignore m_iBuildingsBuilt
+                            parse_integer_prop(
+                                packet,
+                                "DT_TFPlayerScoringDataExclusive",
+                                "m_iBuildingsBuilt",
+                                parser_state,
+                                |buildings_built| {
+                                    if buildings_built > player_summary.buildings_built {
+                                        player_summary.buildings_built = buildings_built;
+                                    }
+                                },
+                            );
                             parse_integer_prop(
                                 packet,
                                 "DT_TFPlayerScoringDataExclusive",
@@ -322,8 +554,28 @@ impl PlayerSummaryAnalyzer {
                                     }
                                 },
                             );
-                            // ignore m_iCrits
-                            // ignore m_iResupplyPoints
+                            parse_integer_prop(
+                                packet,
+                                "DT_TFPlayerScoringDataExclusive",
+                                "m_iCrits",
+                                parser_state,
+                                |crits| {
+                                    if crits > player_summary.crits {
+                                        player_summary.crits = crits;
+                                    }
+                                },
+                            );
+                            parse_integer_prop(
+                                packet,
+                                "DT_TFPlayerScoringDataExclusive",
+                                "m_iResupplyPoints",
+                                parser_state,
+                                |resupply_points| {
+                                    if resupply_points > player_summary.resupply_points {
+                                        player_summary.resupply_points = resupply_points;
+                                    }
+                                },
+                            );
                             parse_integer_prop(
                                 packet,
                                 "DT_TFPlayerScoringDataExclusive",
@@ -365,29 +617,57 @@ impl PlayerSummaryAnalyzer {
                     }
                 }
                 "CTFPlayerResource" => {
-                    // Player summaries - including entity IDs!
-                    // look for props like m_iUserID.<entity_id> = <user_id>
-                    // for example, `m_iUserID.024 = 2523` means entity 24 is user 2523
-                    for i in 0..33 {
-                        // 0 to 32, inclusive (1..33 might also work, not sure if there's a user 0 or not).  Not exhaustive and doesn't work for servers with > 32 players
-                        match packet.get_prop_by_name(
-                            "m_iUserID",
-                            format!("{:0>3}", i).as_str(),
-                            parser_state,
-                        ) {
-                            Some(prop) => {
-                                match prop.value {
-                                    SendPropValue::Integer(x) => {
-                                        let entity_id = EntityId::from(i as u32);
-                                        let user_id = UserId::from(x as u32);
-                                        self.user_id_map.insert(entity_id, user_id);
-                                    }
-                                    _ => {
-                                        // These properties should all be integers...
-                                    }
-                                }
+                    // the per-player arrays are named `<array>.<entity_id>`,
+                    // for example, `m_iDamage.024 = 2523` is the damage of the player in entity 24
+                    let props: Vec<_> = packet
+                        .props(parser_state)
+                        .filter_map(|prop| {
+                            let (table_name, prop_name) = prop.identifier.names()?;
+                            let entity_id = prop_name.as_str().parse::<u32>().ok()?;
+                            Some((table_name, entity_id, prop_value(&prop.value)))
+                        })
+                        .collect();
+
+                    // the slot state can change in the same update as the stats
+                    for (table_name, entity_id, value) in props.iter() {
+                        let slot = self.resource_slots.entry(*entity_id).or_default();
+                        match table_name.as_str() {
+                            "m_bConnected" => slot.connected = *value != 0,
+                            "m_iTeam" => slot.team = Team::new(*value),
+                            _ => {}
+                        }
+                    }
+
+                    for (table_name, entity_id, value) in props {
+                        // skip empty slots and spectators
+                        let active = self
+                            .resource_slots
+                            .get(&entity_id)
+                            .map(|slot| slot.connected && slot.team.is_player())
+                            .unwrap_or_default();
+                        if !active {
+                            continue;
+                        }
+                        if let Some(user_id) =
+                            parser_state.players.user_id(EntityId::from(entity_id))
+                        {
+                            let player_summary =
+                                self.state.player_summaries.entry(user_id).or_default();
+                            let scoreboard = &mut player_summary.scoreboard;
+                            match table_name.as_str() {
+                                "m_iScore" => scoreboard.score = value,
+                                "m_iTotalScore" => scoreboard.total_score = value,
+                                "m_iPing" => scoreboard.ping = value,
+                                "m_iDamage" => scoreboard.damage = value,
+                                "m_iHealing" => scoreboard.healing = value,
+                                "m_iHealingAssist" => scoreboard.healing_assist = value,
+                                "m_iDamageAssist" => scoreboard.damage_assist = value,
+                                "m_iMaxBuffedHealth" => scoreboard.max_buffed_health = value,
+                                _ => {}
                             }
-                            None => {} // ignore, no property for this entity was included
+                            player_summary.support = scoreboard
+                                .healing_assist
+                                .saturating_add(scoreboard.damage_assist);
                         }
                     }
                 }
@@ -397,25 +677,101 @@ impl PlayerSummaryAnalyzer {
             }
         }
     }
+}
 
-    fn parse_user_info(
-        &mut self,
-        index: usize,
-        text: Option<&str>,
-        data: Option<Stream>,
-    ) -> ReadResult<()> {
-        if let Some(user_info) =
-            crate::demo::data::UserInfo::parse_from_string_table(index as u16, text, data)?
-        {
-            self.state
-                .users
-                .entry(user_info.player_info.user_id.into())
-                .and_modify(|info| {
-                    info.entity_id = user_info.entity_id;
-                })
-                .or_insert_with(|| user_info.into());
-        }
+#[test]
+fn test_round_summaries() {
+    use crate::demo::gameevent_gen::{TeamPlayRoundStartEvent, TeamPlayRoundWinEvent};
+    use crate::demo::parser::test_util::{event_message as event, *};
 
-        Ok(())
-    }
+    let mut parser_state = parser_state();
+    parser_state.server_classes = server_classes(&["CTFPlayer", "CTFPlayerResource"]);
+    add_user(&mut parser_state, 0, 2);
+
+    let scores = |kills: i64, damage: i64| {
+        entities_message(vec![
+            packet_entity(
+                0,
+                1,
+                vec![
+                    (
+                        "DT_TFPlayerScoringDataExclusive",
+                        "m_iKills",
+                        SendPropValue::Integer(kills),
+                    ),
+                    (
+                        "DT_TFPlayerScoringDataExclusive",
+                        "m_iDamageDone",
+                        SendPropValue::Integer(damage),
+                    ),
+                ],
+            ),
+            packet_entity(
+                1,
+                30,
+                vec![
+                    ("m_bConnected", "001", SendPropValue::Integer(1)),
+                    ("m_iTeam", "001", SendPropValue::Integer(2)),
+                    ("m_iDamage", "001", SendPropValue::Integer(damage)),
+                ],
+            ),
+        ])
+    };
+    let round_start = || {
+        event(GameEvent::TeamPlayRoundStart(TeamPlayRoundStartEvent {
+            full_reset: false,
+        }))
+    };
+
+    let mut analyser = PlayerSummaryAnalyzer::new();
+    let mut handle = |tick: u32, message: Message| {
+        let tick = DemoTick::from(tick);
+        analyser.handle_packet_meta(tick, &MessagePacketMeta::default(), &parser_state);
+        analyser.handle_message(&message, tick, &parser_state);
+    };
+    handle(10, round_start());
+    handle(20, scores(3, 200));
+    handle(
+        100,
+        event(GameEvent::TeamPlayRoundWin(TeamPlayRoundWinEvent {
+            team: Team::Red as u8,
+            win_reason: 0,
+            flag_cap_limit: 0,
+            full_round: 0,
+            round_time: 0.0,
+            losing_team_num_caps: 0,
+            was_sudden_death: 0,
+        })),
+    );
+    // a kill after the round was won counts towards the won round
+    handle(110, scores(4, 300));
+    handle(120, round_start());
+    // the demo ends during the second round
+    handle(150, scores(6, 450));
+    let state = analyser.into_output(&parser_state);
+
+    let user = UserId::from(2u16);
+    let total = &state.player_summaries[&user];
+    assert_eq!(6, total.kills);
+    assert_eq!(450, total.damage_dealt);
+    assert_eq!(450, total.scoreboard.damage);
+
+    assert_eq!(2, state.rounds.len());
+    let first = &state.rounds[0];
+    assert_eq!(Some(Team::Red), first.winner);
+    assert_eq!(DemoTick::from(10), first.start_tick);
+    assert_eq!(DemoTick::from(100), first.end_tick);
+    assert_eq!(4, first.player_summaries[&user].kills);
+    assert_eq!(300, first.player_summaries[&user].scoreboard.damage);
+    let second = &state.rounds[1];
+    assert_eq!(None, second.winner);
+    assert_eq!(DemoTick::from(120), second.start_tick);
+    assert_eq!(DemoTick::from(150), second.end_tick);
+    assert_eq!(2, second.player_summaries[&user].kills);
+
+    // the rounds add up to the match totals for every field
+    assert_eq!(
+        &second.player_summaries[&user],
+        &total.since(&first.player_summaries[&user])
+    );
 }
@@ -0,0 +1,227 @@
+use crate::demo::data::SteamId;
+use crate::demo::message::packetentities::EntityId;
+use crate::demo::packet::stringtable::StringTableEntry;
+use crate::demo::parser::analyser::UserId;
+use crate::ReadResult;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// The identity of a player as announced in the `userinfo` string table
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(from = "SerializedIdentity", into = "SerializedIdentity")]
+pub struct PlayerIdentity {
+    pub user_id: UserId,
+    /// The last entity used by the player
+    pub entity_id: EntityId,
+    pub steam_id: SteamId,
+    pub name: String,
+}
+
+/// Serialized form of a [`PlayerIdentity`], with the kind of steam id next to the raw id
+#[derive(Serialize, Deserialize)]
+struct SerializedIdentity {
+    user_id: UserId,
+    entity_id: EntityId,
+    steam_id: SteamId,
+    name: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    bot: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    hltv: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+impl From<PlayerIdentity> for SerializedIdentity {
+    fn from(identity: PlayerIdentity) -> Self {
+        SerializedIdentity {
+            bot: identity.steam_id.is_bot(),
+            hltv: identity.steam_id.is_hltv(),
+            user_id: identity.user_id,
+            entity_id: identity.entity_id,
+            steam_id: identity.steam_id,
+            name: identity.name,
+        }
+    }
+}
+
+impl From<SerializedIdentity> for PlayerIdentity {
+    fn from(identity: SerializedIdentity) -> Self {
+        PlayerIdentity {
+            user_id: identity.user_id,
+            entity_id: identity.entity_id,
+            steam_id: SteamId::new(identity.steam_id.as_str(), identity.bot, identity.hltv),
+            name: identity.name,
+        }
+    }
+}
+
+/// All players seen during the demo, maintained by the [`ParserState`](crate::ParserState)
+///
+/// User ids are unique per connection, a player that reconnects gets a new user id with the same steam id.
+/// Entity ids are only unique among the currently connected players and get reused when a player leaves,
+/// so entity lookups always resolve to the current occupant of the entity.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PlayerRegistry {
+    players: BTreeMap<UserId, PlayerIdentity>,
+    /// The player currently using every entity
+    entities: HashMap<EntityId, UserId>,
+    /// The player for every entry in the `userinfo` table
+    table_entries: HashMap<usize, UserId>,
+}
+
+impl PlayerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn handle_user_info(
+        &mut self,
+        index: usize,
+        entry: &StringTableEntry,
+    ) -> ReadResult<()> {
+        let user_info = crate::demo::data::UserInfo::parse_from_string_table(
+            index as u16,
+            entry.text.as_ref().map(|s| s.as_ref()),
+            entry.extra_data.as_ref().map(|data| data.data.clone()),
+        )?;
+
+        // the entry is cleared when the player disconnects
+        if let Some(previous) = self.table_entries.remove(&index) {
+            if let Some(identity) = self.players.get(&previous) {
+                if self.entities.get(&identity.entity_id) == Some(&previous) {
+                    self.entities.remove(&identity.entity_id);
+                }
+            }
+        }
+
+        if let Some(user_info) = user_info {
+            let info = user_info.player_info;
+            let identity = PlayerIdentity {
+                user_id: info.user_id,
+                entity_id: user_info.entity_id,
+                steam_id: info.steam_id,
+                name: info.name,
+            };
+            self.entities.insert(identity.entity_id, identity.user_id);
+            self.table_entries.insert(index, identity.user_id);
+            self.players.insert(identity.user_id, identity);
+        }
+
+        Ok(())
+    }
+
+    /// Get a player by user id, including players that already left
+    pub fn get(&self, user_id: UserId) -> Option<&PlayerIdentity> {
+        self.players.get(&user_id)
+    }
+
+    /// Get the player currently using the entity
+    pub fn get_by_entity(&self, entity_id: EntityId) -> Option<&PlayerIdentity> {
+        self.entities
+            .get(&entity_id)
+            .and_then(|user_id| self.players.get(user_id))
+    }
+
+    /// Get the player stored in an entry of the `userinfo` table
+    pub fn get_by_table_index(&self, index: usize) -> Option<&PlayerIdentity> {
+        self.table_entries
+            .get(&index)
+            .and_then(|user_id| self.players.get(user_id))
+    }
+
+    /// Get the user id of the player currently using the entity
+    pub fn user_id(&self, entity_id: EntityId) -> Option<UserId> {
+        self.entities.get(&entity_id).copied()
+    }
+
+    /// Get the entity of the player, if the player is still connected
+    pub fn entity_id(&self, user_id: UserId) -> Option<EntityId> {
+        let identity = self.players.get(&user_id)?;
+        (self.entities.get(&identity.entity_id) == Some(&user_id)).then_some(identity.entity_id)
+    }
+
+    pub fn steam_id(&self, user_id: UserId) -> Option<&SteamId> {
+        self.players
+            .get(&user_id)
+            .map(|identity| &identity.steam_id)
+    }
+
+    /// Get all user ids used by a steam id, a player gets a new user id every time they reconnect
+    pub fn user_ids<'a>(&'a self, steam_id: &'a SteamId) -> impl Iterator<Item = UserId> + 'a {
+        self.players
+            .values()
+            .filter(move |identity| identity.steam_id == *steam_id)
+            .map(|identity| identity.user_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PlayerIdentity> {
+        self.players.values()
+    }
+
+    /// Get the players from the `cappers` of a capture event, which contains a byte with the entity id of every capping player
+    pub fn cappers(&self, cappers: &[u8]) -> Vec<UserId> {
+        cappers
+            .iter()
+            .filter_map(|entity| self.user_id(EntityId::from(*entity as u32)))
+            .collect()
+    }
+}
+
+#[test]
+fn test_slot_reuse() {
+    use crate::demo::parser::test_util::user_info_entry as entry;
+
+    let mut registry = PlayerRegistry::new();
+    registry.handle_user_info(2, &entry(10, "[U:1:1]")).unwrap();
+    registry.handle_user_info(3, &entry(11, "[U:1:2]")).unwrap();
+
+    // the first player leaves and their slot is taken by another player
+    registry
+        .handle_user_info(2, &StringTableEntry::default())
+        .unwrap();
+    assert_eq!(None, registry.user_id(EntityId::from(3u32)));
+    registry.handle_user_info(2, &entry(12, "[U:1:3]")).unwrap();
+    assert_eq!(
+        Some(UserId::from(12u16)),
+        registry.user_id(EntityId::from(3u32))
+    );
+    assert_eq!(None, registry.entity_id(UserId::from(10u16)));
+    assert_eq!(
+        Some(&SteamId::from("[U:1:1]")),
+        registry.steam_id(UserId::from(10u16))
+    );
+
+    // the first player reconnects in a new slot
+    registry.handle_user_info(4, &entry(13, "[U:1:1]")).unwrap();
+    assert_eq!(
+        vec![UserId::from(10u16), UserId::from(13u16)],
+        registry
+            .user_ids(&SteamId::from("[U:1:1]"))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        Some(EntityId::from(5u32)),
+        registry.entity_id(UserId::from(13u16))
+    );
+}
+
+#[test]
+fn test_identity_serialize() {
+    let identity = PlayerIdentity {
+        user_id: UserId::from(1u16),
+        entity_id: EntityId::from(1u32),
+        steam_id: SteamId::new("BOT", true, true),
+        name: "SourceTV".into(),
+    };
+    let json = serde_json::to_string(&identity).unwrap();
+    assert_eq!(
+        r#"{"user_id":1,"entity_id":1,"steam_id":"BOT","name":"SourceTV","hltv":true}"#,
+        json
+    );
+    let deserialized: PlayerIdentity = serde_json::from_str(&json).unwrap();
+    assert!(deserialized.steam_id.is_hltv());
+    assert_eq!(identity, deserialized);
+}
@@ -15,6 +15,8 @@ use crate::demo::packet::datatable::{
 use crate::demo::packet::stringtable::StringTableEntry;
 
 use crate::demo::data::DemoTick;
+use crate::demo::parser::entitystore::EntityStore;
+use crate::demo::parser::registry::PlayerRegistry;
 use crate::demo::sendprop::{SendProp, SendPropIdentifier};
 use crate::nullhasher::NullHasherBuilder;
 use crate::{Result, Stream};
@@ -30,9 +32,14 @@ pub struct DemoMeta {
     pub interval_per_tick: f32,
 }
 
-#[derive(Clone)]
+/// The state of the parser
+///
+/// The state can be (de)serialized to store a checkpoint of the parser,
+/// the message types handled by the analyser are not stored and need to be restored with [`ParserState::set_analyser_handles`].
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ParserState {
     pub static_baselines: HashMap<ClassId, StaticBaseline, NullHasherBuilder>,
+    #[serde(skip)]
     pub parsed_static_baselines: RefCell<HashMap<ClassId, Vec<SendProp>, NullHasherBuilder>>,
     pub event_definitions: Vec<GameEventDefinition>,
     pub string_tables: Vec<StringTableMeta>,
@@ -42,13 +49,24 @@ pub struct ParserState {
     pub server_classes: Vec<ServerClass>,
     pub instance_baselines: [Baseline; 2],
     pub demo_meta: DemoMeta,
+    /// The players in the demo, shared by all analysers
+    #[serde(default)]
+    pub players: PlayerRegistry,
+    /// The current props of every entity, only maintained when enabled with [`ParserState::enable_entity_store`]
+    #[serde(default)]
+    pub entity_store: Option<EntityStore>,
+    #[serde(skip, default = "default_analyser_handles")]
     analyser_handles: fn(message_type: MessageType) -> bool,
     handle_entities: bool,
     parse_all: bool,
     pub protocol_version: u32,
 }
 
-#[derive(Clone)]
+fn default_analyser_handles() -> fn(message_type: MessageType) -> bool {
+    |_| false
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct StaticBaseline {
     pub class_id: ClassId,
     pub raw: Stream<'static>,
@@ -87,6 +105,8 @@ impl<'a> ParserState {
             server_classes: Vec::new(),
             instance_baselines: [Baseline::default(), Baseline::default()],
             demo_meta: DemoMeta::default(),
+            players: PlayerRegistry::default(),
+            entity_store: None,
             analyser_handles,
             handle_entities: analyser_handles(MessageType::PacketEntities) || parse_all,
             parse_all,
@@ -94,6 +114,35 @@ impl<'a> ParserState {
         }
     }
 
+    /// Set the message types handled by the analyser, used when restoring a deserialized state
+    pub fn set_analyser_handles(
+        &mut self,
+        analyser_handles: fn(message_type: MessageType) -> bool,
+    ) {
+        self.analyser_handles = analyser_handles;
+        self.handle_entities = analyser_handles(MessageType::PacketEntities)
+            || self.parse_all
+            || self.entity_store.is_some();
+    }
+
+    /// Keep track of the props of all entities in the [`EntityStore`]
+    ///
+    /// This needs to be enabled before the data tables are parsed
+    pub fn enable_entity_store(&mut self) {
+        self.entity_store.get_or_insert_with(EntityStore::default);
+        self.handle_entities = true;
+    }
+
+    /// Apply an entity update to the entity store, if enabled
+    ///
+    /// This needs to be called before the message is passed to [`ParserState::handle_message`]
+    pub fn update_entity_store(&mut self, message: &PacketEntitiesMessage) {
+        if let Some(mut store) = self.entity_store.take() {
+            store.handle_message(message, self);
+            self.entity_store = Some(store);
+        }
+    }
+
     pub fn get_static_baseline(
         &self,
         class_id: ClassId,
@@ -278,12 +327,13 @@ impl<'a> ParserState {
         }
     }
 
-    pub fn handle_string_entry(
-        &mut self,
-        table: &str,
-        _index: usize,
-        entry: &StringTableEntry<'a>,
-    ) {
+    pub fn handle_string_entry(&mut self, table: &str, index: usize, entry: &StringTableEntry<'a>) {
+        if table == "userinfo" {
+            if let Err(_e) = self.players.handle_user_info(index, entry) {
+                #[cfg(feature = "trace")]
+                warn!(index, error = display(_e), "malformed user info");
+            }
+        }
         if table == "instancebaseline" {
             if let (Some(extra), Ok(class_id)) = (&entry.extra_data, entry.text().parse()) {
                 let baseline = StaticBaseline::new(class_id, extra.data.to_owned());
@@ -304,7 +354,7 @@ impl<'a> ParserState {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Baseline {
     instances: Vec<Option<BaselineEntity>>,
 }
@@ -357,7 +407,7 @@ impl Baseline {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BaselineEntity {
     pub entity_id: EntityId,
     pub server_class: ClassId,
@@ -0,0 +1,173 @@
+//! Builders for the messages and parser state used by the analyser tests
+
+use crate::demo::data::userinfo::{PlayerInfo, UserInfo};
+use crate::demo::data::SteamId;
+use crate::demo::gameevent_gen::PlayerDeathEvent;
+use crate::demo::gamevent::GameEvent;
+use crate::demo::message::gameevent::{GameEventMessage, GameEventTypeId};
+use crate::demo::message::packetentities::{
+    EntityId, PacketEntitiesMessage, PacketEntity, UpdateType,
+};
+use crate::demo::message::{Message, ServerInfoMessage};
+use crate::demo::packet::datatable::{ClassId, ServerClass};
+use crate::demo::packet::stringtable::StringTableEntry;
+use crate::demo::parser::analyser::UserId;
+use crate::demo::sendprop::{SendProp, SendPropIdentifier, SendPropValue};
+use crate::ParserState;
+
+/// A parser state without any players, data tables or string tables
+pub fn parser_state() -> ParserState {
+    ParserState::new(24, |_| false, false)
+}
+
+/// The `userinfo` string table entry for a player
+///
+/// The entry has no text, so the entity of the player is derived from the table index.
+pub fn user_info_entry(user_id: u16, steam_id: &str) -> StringTableEntry<'static> {
+    StringTableEntry {
+        text: None,
+        ..UserInfo {
+            entity_id: EntityId::default(),
+            player_info: PlayerInfo {
+                name: format!("player {}", user_id),
+                user_id: UserId::from(user_id),
+                steam_id: SteamId::from(steam_id),
+                ..PlayerInfo::default()
+            },
+        }
+        .encode_to_string_table()
+        .unwrap()
+    }
+}
+
+/// Add a player with the steam id `[U:1:<user_id>]` to the player registry
+///
+/// The player gets the entity `index + 1`, the `userinfo` entry is returned for analysers that handle it themselves.
+pub fn add_user(
+    parser_state: &mut ParserState,
+    index: usize,
+    user_id: u16,
+) -> StringTableEntry<'static> {
+    let entry = user_info_entry(user_id, &format!("[U:1:{}]", user_id));
+    parser_state
+        .players
+        .handle_user_info(index, &entry)
+        .unwrap();
+    entry
+}
+
+/// Server classes with the class id matching the index in `names`
+pub fn server_classes(names: &[&str]) -> Vec<ServerClass> {
+    names
+        .iter()
+        .enumerate()
+        .map(|(id, name)| ServerClass {
+            id: ClassId::from(id as u16),
+            name: (*name).into(),
+            data_table: Default::default(),
+        })
+        .collect()
+}
+
+/// An update for an entity that is already in the pvs
+///
+/// The props are given as `(table, name, value)`, player resource props use the prop name
+/// as the table and the zero padded player slot as name.
+pub fn packet_entity(
+    class: u16,
+    index: u32,
+    props: Vec<(&str, &str, SendPropValue)>,
+) -> PacketEntity {
+    PacketEntity {
+        server_class: ClassId::from(class),
+        entity_index: EntityId::from(index),
+        props: props
+            .into_iter()
+            .map(|(table, name, value)| SendProp {
+                index: 0,
+                identifier: SendPropIdentifier::new(table, name),
+                value,
+            })
+            .collect(),
+        in_pvs: true,
+        update_type: UpdateType::Preserve,
+        serial_number: 0,
+        delay: None,
+        delta: None,
+        baseline_index: 0,
+    }
+}
+
+/// A full entity update containing `entities`
+pub fn entities_message(entities: Vec<PacketEntity>) -> Message<'static> {
+    Message::PacketEntities(PacketEntitiesMessage {
+        entities,
+        removed_entities: vec![],
+        max_entries: 0,
+        delta: None,
+        base_line: 0,
+        updated_base_line: false,
+    })
+}
+
+/// A game event message, the event type id isn't used by the analysers
+pub fn event_message(event: GameEvent) -> Message<'static> {
+    Message::GameEvent(GameEventMessage {
+        event_type_id: GameEventTypeId::from(0),
+        event,
+    })
+}
+
+/// The server info for a demo on `map` with a tickrate of 66.67
+pub fn server_info_message(map: &str) -> Message<'static> {
+    Message::ServerInfo(Box::new(ServerInfoMessage {
+        version: 24,
+        server_count: 0,
+        stv: true,
+        dedicated: true,
+        max_crc: 0,
+        max_classes: 1,
+        map_hash: [0; 16],
+        player_slot: 0,
+        max_player_count: 24,
+        interval_per_tick: 0.015,
+        platform: "l".into(),
+        game: "tf".into(),
+        map: map.into(),
+        skybox: String::new(),
+        server_name: String::new(),
+        replay: false,
+    }))
+}
+
+/// A death without any flags, `attacker` is 0 for deaths not caused by a player
+pub fn death_event(user_id: u16, attacker: u16, weapon: &str) -> PlayerDeathEvent {
+    PlayerDeathEvent {
+        user_id,
+        victim_ent_index: 0,
+        inflictor_ent_index: 0,
+        attacker,
+        weapon: weapon.into(),
+        weapon_id: 0,
+        damage_bits: 0,
+        custom_kill: 0,
+        assister: 0,
+        weapon_log_class_name: weapon.into(),
+        stun_flags: 0,
+        death_flags: 0,
+        silent_kill: false,
+        player_penetrate_count: 0,
+        assister_fallback: "".into(),
+        kill_streak_total: 0,
+        kill_streak_wep: 0,
+        kill_streak_assist: 0,
+        kill_streak_victim: 0,
+        ducks_streaked: 0,
+        duck_streak_total: 0,
+        duck_streak_assist: 0,
+        duck_streak_victim: 0,
+        rocket_jump: false,
+        weapon_def_index: 0,
+        crit_type: 0,
+    }
+}
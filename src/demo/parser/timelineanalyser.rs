@@ -0,0 +1,323 @@
+use crate::demo::data::{DemoTick, SteamId};
+use crate::demo::gameevent_gen::PlayerTeamEvent;
+use crate::demo::gamevent::GameEvent;
+use crate::demo::message::gameevent::GameEventMessage;
+use crate::demo::message::{Message, MessageType};
+use crate::demo::packet::stringtable::StringTableEntry;
+use crate::demo::parser::analyser::{Team, UserId};
+use crate::demo::parser::handler::{BorrowMessageHandler, MessageHandler};
+use crate::ParserState;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// A name used by a player
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NameSpan {
+    pub name: String,
+    pub start_tick: DemoTick,
+    /// The tick the player changed their name or disconnected, `None` if the name was used until the end of the demo
+    pub end_tick: Option<DemoTick>,
+}
+
+impl NameSpan {
+    pub fn contains(&self, tick: DemoTick) -> bool {
+        self.start_tick <= tick && self.end_tick.map(|end| tick < end).unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UserEventKind {
+    Connect,
+    /// Disconnecting, the reason is only known when the game sent a disconnect event
+    Disconnect {
+        reason: Option<String>,
+    },
+    TeamChange {
+        from: Team,
+        to: Team,
+    },
+    NameChange {
+        from: String,
+        to: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserEvent {
+    pub tick: DemoTick,
+    #[serde(flatten)]
+    pub kind: UserEventKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserTimeline {
+    pub user_id: UserId,
+    pub steam_id: Option<SteamId>,
+    pub names: Vec<NameSpan>,
+    pub events: Vec<UserEvent>,
+}
+
+impl UserTimeline {
+    fn new(user_id: UserId) -> Self {
+        UserTimeline {
+            user_id,
+            steam_id: None,
+            names: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Get the name the player used at the tick
+    pub fn name_at(&self, tick: DemoTick) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|span| span.contains(tick))
+            .map(|span| span.name.as_str())
+    }
+
+    fn current_name(&self) -> Option<&str> {
+        self.names
+            .last()
+            .filter(|span| span.end_tick.is_none())
+            .map(|span| span.name.as_str())
+    }
+
+    fn end_name(&mut self, tick: DemoTick) {
+        if let Some(span) = self.names.last_mut() {
+            span.end_tick.get_or_insert(tick);
+        }
+    }
+
+    fn start_name(&mut self, name: String, tick: DemoTick) {
+        self.end_name(tick);
+        self.names.push(NameSpan {
+            name,
+            start_tick: tick,
+            end_tick: None,
+        });
+    }
+}
+
+/// The names, connects, disconnects and team changes of every user
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Timelines {
+    pub users: BTreeMap<UserId, UserTimeline>,
+}
+
+impl Timelines {
+    /// Get the name a user had at the tick
+    pub fn name_at(&self, user_id: UserId, tick: DemoTick) -> Option<&str> {
+        self.users.get(&user_id)?.name_at(tick)
+    }
+
+    /// Get all users that used the name at the tick
+    pub fn named_at<'a>(
+        &'a self,
+        name: &'a str,
+        tick: DemoTick,
+    ) -> impl Iterator<Item = &'a UserTimeline> + 'a {
+        self.users
+            .values()
+            .filter(move |timeline| timeline.name_at(tick) == Some(name))
+    }
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct TimelineAnalyser {
+    state: Timelines,
+    tick: DemoTick,
+    connected: HashSet<UserId>,
+    /// The user for every entry in the `userinfo` table
+    table_entries: HashMap<usize, UserId>,
+}
+
+impl MessageHandler for TimelineAnalyser {
+    type Output = Timelines;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        matches!(message_type, MessageType::GameEvent)
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, _parser_state: &ParserState) {
+        self.tick = tick;
+        if let Message::GameEvent(GameEventMessage { event, .. }) = message {
+            match event {
+                GameEvent::PlayerConnectClient(event) => {
+                    self.connect(UserId::from(event.user_id), event.name.to_string())
+                }
+                GameEvent::PlayerDisconnect(event) => {
+                    self.disconnect(UserId::from(event.user_id), Some(event.reason.to_string()))
+                }
+                GameEvent::PlayerChangeName(event) => {
+                    self.change_name(UserId::from(event.user_id), event.new_name.to_string())
+                }
+                GameEvent::PlayerTeam(event) => self.handle_team(event),
+                _ => {}
+            }
+        }
+    }
+
+    fn handle_string_entry(
+        &mut self,
+        table: &str,
+        index: usize,
+        _entry: &StringTableEntry,
+        parser_state: &ParserState,
+    ) {
+        if table == "userinfo" {
+            let player = parser_state.players.get_by_table_index(index);
+            let previous = self.table_entries.remove(&index);
+            if let Some(previous) = previous {
+                if player.map(|player| player.user_id) != Some(previous) {
+                    self.disconnect(previous, None);
+                }
+            }
+
+            if let Some(player) = player {
+                self.table_entries.insert(index, player.user_id);
+                self.timeline(player.user_id).steam_id = Some(player.steam_id.clone());
+                if self.connected.contains(&player.user_id) {
+                    self.change_name(player.user_id, player.name.clone());
+                } else {
+                    self.connect(player.user_id, player.name.clone());
+                }
+            }
+        }
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self::Output {
+        self.state
+    }
+}
+
+impl BorrowMessageHandler for TimelineAnalyser {
+    type BorrowedOutput<'a> = &'a Self::Output;
+
+    fn borrow_output<'a>(&'a self, _state: &ParserState) -> Self::BorrowedOutput<'a> {
+        &self.state
+    }
+}
+
+impl TimelineAnalyser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn timeline(&mut self, user_id: UserId) -> &mut UserTimeline {
+        self.state
+            .users
+            .entry(user_id)
+            .or_insert_with(|| UserTimeline::new(user_id))
+    }
+
+    fn push_event(&mut self, user_id: UserId, kind: UserEventKind) {
+        let tick = self.tick;
+        self.timeline(user_id).events.push(UserEvent { tick, kind });
+    }
+
+    fn connect(&mut self, user_id: UserId, name: String) {
+        // both the connect event and the userinfo entry announce a connecting player,
+        // and reconnecting players get a new user id
+        let disconnected = self
+            .state
+            .users
+            .get(&user_id)
+            .and_then(|timeline| timeline.events.last())
+            .map(|event| matches!(event.kind, UserEventKind::Disconnect { .. }))
+            .unwrap_or_default();
+        if disconnected || !self.connected.insert(user_id) {
+            return;
+        }
+        let tick = self.tick;
+        self.push_event(user_id, UserEventKind::Connect);
+        self.timeline(user_id).start_name(name, tick);
+    }
+
+    fn disconnect(&mut self, user_id: UserId, reason: Option<String>) {
+        let tick = self.tick;
+        if self.connected.remove(&user_id) {
+            self.push_event(user_id, UserEventKind::Disconnect { reason });
+            self.timeline(user_id).end_name(tick);
+        } else if let Some(UserEvent {
+            kind:
+                UserEventKind::Disconnect {
+                    reason: last_reason @ None,
+                },
+            ..
+        }) = self
+            .state
+            .users
+            .get_mut(&user_id)
+            .and_then(|timeline| timeline.events.last_mut())
+        {
+            // the userinfo entry was cleared before the disconnect event
+            *last_reason = reason;
+        }
+    }
+
+    fn change_name(&mut self, user_id: UserId, name: String) {
+        let tick = self.tick;
+        let timeline = self.timeline(user_id);
+        match timeline.current_name() {
+            Some(current) if current == name => {}
+            Some(current) => {
+                let from = current.to_string();
+                timeline.start_name(name.clone(), tick);
+                self.push_event(user_id, UserEventKind::NameChange { from, to: name });
+            }
+            None => timeline.start_name(name, tick),
+        }
+    }
+
+    fn handle_team(&mut self, event: &PlayerTeamEvent) {
+        if event.disconnect || event.team == event.old_team {
+            return;
+        }
+        self.push_event(
+            UserId::from(event.user_id),
+            UserEventKind::TeamChange {
+                from: Team::new(event.old_team),
+                to: Team::new(event.team),
+            },
+        );
+    }
+}
+
+#[test]
+fn test_name_history() {
+    let user = UserId::from(7u16);
+    let mut analyser = TimelineAnalyser::new();
+
+    analyser.tick = DemoTick::from(100);
+    analyser.connect(user, "player".into());
+    analyser.tick = DemoTick::from(40000);
+    analyser.change_name(user, "xX_name_Xx".into());
+    analyser.tick = DemoTick::from(45000);
+    analyser.disconnect(user, None);
+    analyser.disconnect(user, Some("Disconnect by user.".into()));
+
+    let state = analyser.into_output(&ParserState::new(24, |_| false, false));
+    assert_eq!(Some("player"), state.name_at(user, DemoTick::from(39999)));
+    assert_eq!(
+        Some("xX_name_Xx"),
+        state.name_at(user, DemoTick::from(40000))
+    );
+    assert_eq!(None, state.name_at(user, DemoTick::from(45000)));
+    assert_eq!(
+        vec![user],
+        state
+            .named_at("xX_name_Xx", DemoTick::from(40000))
+            .map(|timeline| timeline.user_id)
+            .collect::<Vec<_>>()
+    );
+
+    let events = &state.users[&user].events;
+    assert_eq!(3, events.len());
+    assert_eq!(
+        UserEventKind::Disconnect {
+            reason: Some("Disconnect by user.".into())
+        },
+        events[2].kind
+    );
+}
@@ -12,6 +12,12 @@ pub struct Vector {
     pub z: f32,
 }
 
+impl Vector {
+    pub fn length(&self) -> f32 {
+        (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+}
+
 impl From<Vector> for [f32; 3] {
     fn from(vec: Vector) -> Self {
         [vec.x, vec.y, vec.z]
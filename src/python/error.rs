@@ -0,0 +1,173 @@
+use crate::demo::data::DemoTick;
+use pyo3::exceptions::PyOSError;
+use pyo3::prelude::*;
+use pyo3::{create_exception, import_exception};
+
+// pyo3 doesn't provide the warning categories
+import_exception!(builtins, UserWarning);
+
+create_exception!(
+    tf_demo_parser,
+    ParseError,
+    PyOSError,
+    "Base class for all errors that occur while parsing a demo"
+);
+create_exception!(
+    tf_demo_parser,
+    TruncatedDemoError,
+    ParseError,
+    "The demo ended without a stop packet, all data before the end is intact"
+);
+create_exception!(
+    tf_demo_parser,
+    MalformedDemoError,
+    ParseError,
+    "The demo contains invalid data"
+);
+create_exception!(
+    tf_demo_parser,
+    ReadError,
+    MalformedDemoError,
+    "Invalid data was encountered while reading from the demo"
+);
+create_exception!(
+    tf_demo_parser,
+    UnknownServerClassError,
+    MalformedDemoError,
+    "An entity with an unknown server class was encountered"
+);
+create_exception!(
+    tf_demo_parser,
+    GameEventError,
+    MalformedDemoError,
+    "A game event doesn't match its definition"
+);
+create_exception!(
+    tf_demo_parser,
+    SendPropError,
+    MalformedDemoError,
+    "A send table or send prop is invalid or doesn't match its definition"
+);
+
+create_exception!(
+    tf_demo_parser,
+    TruncatedDemoWarning,
+    UserWarning,
+    "The demo ended without a stop packet, the returned data only covers the demo up to that point"
+);
+
+/// A parse error together with the location in the demo where it occurred
+#[derive(Debug)]
+pub struct DemoError {
+    /// The error that stopped the parser, `None` if the demo was truncated
+    pub error: Option<crate::ParseError>,
+    /// The tick of the last packet that was processed
+    pub tick: Option<DemoTick>,
+    /// The position in bits of the packet that failed to parse
+    pub bit_position: Option<usize>,
+}
+
+impl DemoError {
+    pub fn new(error: crate::ParseError, tick: DemoTick, bit_position: usize) -> Self {
+        DemoError {
+            error: Some(error),
+            tick: Some(tick),
+            bit_position: Some(bit_position),
+        }
+    }
+
+    /// The demo ended without a stop packet, `bit_position` is the end of the last complete packet
+    pub fn truncated(tick: DemoTick, bit_position: usize) -> Self {
+        DemoError {
+            error: None,
+            tick: Some(tick),
+            bit_position: Some(bit_position),
+        }
+    }
+}
+
+impl From<crate::ParseError> for DemoError {
+    fn from(error: crate::ParseError) -> Self {
+        DemoError {
+            error: Some(error),
+            tick: None,
+            bit_position: None,
+        }
+    }
+}
+
+impl From<crate::ParseError> for PyErr {
+    fn from(err: crate::ParseError) -> PyErr {
+        DemoError::from(err).into()
+    }
+}
+
+impl From<DemoError> for PyErr {
+    fn from(err: DemoError) -> PyErr {
+        use crate::ParseError as E;
+
+        let message = match &err.error {
+            Some(error) => error.to_string(),
+            None => "The demo ended unexpectedly".to_string(),
+        };
+        let mut entity_id = None;
+        let mut class_id = None;
+        let py_err = match err.error {
+            None => TruncatedDemoError::new_err(message),
+            Some(E::Io(e)) => return e.into(),
+            // running out of data inside a packet means the packet is corrupt,
+            // a demo that ends between packets is reported as truncated
+            Some(E::ReadError(_) | E::MalformedUTF8(_)) => ReadError::new_err(message),
+            Some(E::UnknownServerClass(class)) => {
+                class_id = Some(u16::from(class));
+                UnknownServerClassError::new_err(message)
+            }
+            Some(E::MalformedGameEvent(_) | E::InvalidGameEvent { .. }) => {
+                GameEventError::new_err(message)
+            }
+            Some(
+                E::InvalidSendPropType(_)
+                | E::InvalidSendProp(_)
+                | E::UnknownSendTable(_)
+                | E::UnknownDefinition(_)
+                | E::PropIndexOutOfBounds { .. },
+            ) => SendPropError::new_err(message),
+            Some(E::UnknownEntity(entity)) => {
+                entity_id = Some(u32::from(entity));
+                MalformedDemoError::new_err(message)
+            }
+            Some(_) => MalformedDemoError::new_err(message),
+        };
+
+        Python::with_gil(|py| {
+            let value = py_err.value(py);
+            let attributes = [
+                ("entity_id", entity_id.into_py(py)),
+                ("class_id", class_id.into_py(py)),
+                ("tick", err.tick.map(u32::from).into_py(py)),
+                ("bit_position", err.bit_position.into_py(py)),
+            ];
+            for (name, attribute) in attributes {
+                if let Err(e) = value.setattr(name, attribute) {
+                    return e;
+                }
+            }
+            py_err.clone_ref(py)
+        })
+    }
+}
+
+pub fn register(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add("ParseError", py.get_type::<ParseError>())?;
+    m.add("TruncatedDemoError", py.get_type::<TruncatedDemoError>())?;
+    m.add("TruncatedDemoWarning", py.get_type::<TruncatedDemoWarning>())?;
+    m.add("MalformedDemoError", py.get_type::<MalformedDemoError>())?;
+    m.add("ReadError", py.get_type::<ReadError>())?;
+    m.add(
+        "UnknownServerClassError",
+        py.get_type::<UnknownServerClassError>(),
+    )?;
+    m.add("GameEventError", py.get_type::<GameEventError>())?;
+    m.add("SendPropError", py.get_type::<SendPropError>())?;
+    Ok(())
+}
@@ -0,0 +1,450 @@
+use crate::demo::header::Header;
+use crate::demo::parser::MessageHandler;
+use crate::{
+    Analyser, BuildingClass, Class, Demo, DemoParser, GameStateAnalyser, PlayerSummaryAnalyzer,
+    Team, UserId,
+};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::wrap_pyfunction;
+use std::fs;
+
+mod error;
+mod ticker;
+mod types;
+
+pub use error::DemoError;
+pub use types::*;
+
+/// Parse a demo, adding the location in the demo to any error that occurs
+///
+/// A truncated demo only raises a `TruncatedDemoError` when `strict` is set,
+/// otherwise a `TruncatedDemoWarning` is emitted and the data up to the end of the demo is returned.
+fn parse_demo<A: MessageHandler>(
+    py: Python,
+    demo: &Demo,
+    analyser: A,
+    strict: bool,
+) -> PyResult<(Header, A::Output)> {
+    let (header, mut ticker) = DemoParser::new_all_with_analyser(demo.get_stream(), analyser)
+        .ticker()
+        .map_err(|error| DemoError {
+            error: Some(error),
+            tick: None,
+            bit_position: Some(0),
+        })?;
+    loop {
+        let pos = ticker.pos();
+        match ticker.tick() {
+            Ok(true) => {}
+            Ok(false) if ticker.incomplete() => {
+                if strict {
+                    return Err(DemoError::truncated(ticker.demo_tick(), pos).into());
+                }
+                PyErr::warn(
+                    py,
+                    py.get_type::<error::TruncatedDemoWarning>(),
+                    &format!(
+                        "The demo ended unexpectedly after tick {}",
+                        ticker.demo_tick()
+                    ),
+                    1,
+                )?;
+                break;
+            }
+            Ok(false) => break,
+            Err(e) => return Err(DemoError::new(e, ticker.demo_tick(), pos).into()),
+        }
+    }
+    Ok((header, ticker.into_state()))
+}
+
+/// Parse a demo file
+///
+/// Returns a tuple of `(Header, MatchState, GameState, PlayerSummaryState)`,
+/// a truncated demo only raises a `TruncatedDemoError` if `strict` is set.
+#[pyfunction(strict = "false")]
+#[pyo3(text_signature = "(path, strict=False)")]
+fn parse(
+    py: Python,
+    path: String,
+    strict: bool,
+) -> PyResult<(PyHeader, PyMatchState, PyGameState, PyPlayerSummaryState)> {
+    let file = fs::read(path)?;
+    let demo = Demo::new(&file);
+
+    let (header, (match_state, game_state, player_state)) = parse_demo(
+        py,
+        &demo,
+        (
+            Analyser::new(),
+            GameStateAnalyser::new(),
+            PlayerSummaryAnalyzer::new(),
+        ),
+        strict,
+    )?;
+
+    Ok((
+        PyHeader::from(&header),
+        PyMatchState::from(&match_state),
+        PyGameState::from(&game_state),
+        PyPlayerSummaryState::from(&player_state),
+    ))
+}
+
+/// Parse a demo file, keeping the results gathered so far if the demo is truncated or corrupt
+///
+/// Returns a tuple of `(Header, MatchState, GameState, PlayerSummaryState, ParseDiagnostics)`
+#[pyfunction]
+fn parse_partial(
+    py: Python,
+    path: String,
+) -> PyResult<(
+    PyHeader,
+    PyMatchState,
+    PyGameState,
+    PyPlayerSummaryState,
+    PyParseDiagnostics,
+)> {
+    let file = fs::read(path)?;
+    let demo = Demo::new(&file);
+
+    let parser = DemoParser::new_all_with_analyser(
+        demo.get_stream(),
+        (
+            Analyser::new(),
+            GameStateAnalyser::new(),
+            PlayerSummaryAnalyzer::new(),
+        ),
+    );
+    let (header, (match_state, game_state, player_state), diagnostics) =
+        parser.parse_partial().map_err(|error| DemoError {
+            error: Some(error),
+            tick: None,
+            bit_position: Some(0),
+        })?;
+
+    let error = diagnostics.error.map(|error| {
+        let error = DemoError::new(error, diagnostics.last_tick, diagnostics.byte_offset * 8);
+        PyErr::from(error).into_py(py)
+    });
+
+    Ok((
+        PyHeader::from(&header),
+        PyMatchState::from(&match_state),
+        PyGameState::from(&game_state),
+        PyPlayerSummaryState::from(&player_state),
+        PyParseDiagnostics {
+            incomplete: diagnostics.incomplete,
+            last_tick: diagnostics.last_tick.into(),
+            byte_offset: diagnostics.byte_offset,
+            error,
+        },
+    ))
+}
+
+/// Parse multiple demo files in parallel
+///
+/// `analysers` selects which outputs to create, any of "match", "game_state" and "player_summaries",
+/// by default all of them are created.
+/// With `parse_all` disabled only the messages needed by the selected analysers are parsed.
+/// Returns a `ParseResult` for every path, in the same order, a failure to parse one demo doesn't affect the others.
+#[pyfunction(analysers = "None", parse_all = "true")]
+#[pyo3(text_signature = "(paths, analysers=None, parse_all=True)")]
+fn parse_many(
+    py: Python,
+    paths: Vec<String>,
+    analysers: Option<Vec<String>>,
+    parse_all: bool,
+) -> PyResult<Vec<PyParseResult>> {
+    let (match_state, game_state, player_summaries) = match analysers {
+        Some(analysers) => {
+            for analyser in &analysers {
+                if !matches!(
+                    analyser.as_str(),
+                    "match" | "game_state" | "player_summaries"
+                ) {
+                    return Err(PyValueError::new_err(format!(
+                        "Unknown analyser: {}",
+                        analyser
+                    )));
+                }
+            }
+            let enabled = |name: &str| analysers.iter().any(|analyser| analyser == name);
+            (
+                enabled("match"),
+                enabled("game_state"),
+                enabled("player_summaries"),
+            )
+        }
+        None => (true, true, true),
+    };
+
+    let results = py.allow_threads(|| {
+        crate::parse_many(
+            &paths,
+            || {
+                (
+                    match_state.then(Analyser::new),
+                    game_state.then(GameStateAnalyser::new),
+                    player_summaries.then(PlayerSummaryAnalyzer::new),
+                )
+            },
+            parse_all,
+        )
+    });
+
+    Ok(paths
+        .into_iter()
+        .zip(results)
+        .map(|(path, result)| match result {
+            Ok((header, (match_state, game_state, player_summaries))) => PyParseResult {
+                path,
+                header: Some(PyHeader::from(&header)),
+                match_state: match_state.as_ref().map(PyMatchState::from),
+                game_state: game_state.as_ref().map(PyGameState::from),
+                player_summaries: player_summaries.as_ref().map(PyPlayerSummaryState::from),
+                error: None,
+            },
+            Err(e) => PyParseResult {
+                path,
+                header: None,
+                match_state: None,
+                game_state: None,
+                player_summaries: None,
+                error: Some(PyErr::from(DemoError::from(e)).into_py(py)),
+            },
+        })
+        .collect())
+}
+
+#[pyfunction(strict = "false")]
+#[pyo3(name = "main", text_signature = "(path, strict=False)")]
+fn py_main(py: Python, path: String, strict: bool) -> PyResult<String> {
+    let file = fs::read(path)?;
+    let demo = Demo::new(&file);
+
+    // gets scoreboard information, building events, kill events, team, and class information
+    // as well as chat messages, deaths, rounds, and start_tick in a single pass
+    let (header, (player_state, mut game_state, server_state)) = parse_demo(
+        py,
+        &demo,
+        (
+            PlayerSummaryAnalyzer::new(),
+            GameStateAnalyser::new(),
+            Analyser::new(),
+        ),
+        strict,
+    )?;
+
+    // process scoreboard information
+    let scoreboard_table_header = "player,id,points,kills,deaths,assists,destruction,captures,defenses,domination,revenge,ubers,headshots,teleports,healing,backstabs,bonus,support,damage,team,class";
+    let mut scoreboard_data: String = "".to_owned();
+    for (user_id, user_data) in &player_state.users {
+        let player_name = &user_data.name;
+        let player_data = game_state.get_or_create_player(user_data.entity_id);
+        let mut team = player_data.team;
+        let mut tf_class = player_data.class;
+        if team == Team::Other
+        {
+            team = user_data.team;
+        }
+        if tf_class == Class::Other
+        {
+            for (c, _s) in user_data.classes.sorted()
+            {
+                if c != Class::Other
+                {
+                    tf_class = c;
+                    break;
+                }
+            }
+        }
+        let steam_id = &user_data.steam_id;
+        let summary = player_state.player_summaries.get(&user_id);
+        match summary {
+            Some(s) => {
+                let s: String = format!(
+                    "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+                    player_name.replace(",", ""),
+                    steam_id,
+                    s.points,
+                    s.kills,
+                    s.deaths,
+                    s.assists,
+                    s.buildings_destroyed,
+                    s.captures,
+                    s.defenses,
+                    s.dominations,
+                    s.revenges,
+                    s.ubercharges,
+                    s.headshots,
+                    s.teleports,
+                    s.healing,
+                    s.backstabs,
+                    s.bonus_points,
+                    s.support,
+                    s.damage_dealt,
+                    team,
+                    tf_class,
+                );
+                scoreboard_data = scoreboard_data + &s;
+            },
+            None => {
+                // No summary for this player - they likely joined at the end of the match, or left before they did anything noteworthy
+            }
+        }
+    }
+
+    // the users are taken from the player registry, so they cover every player seen in the demo
+    let steam_id = |user_id: UserId| {
+        player_state
+            .users
+            .get(&user_id)
+            .map(|info| info.steam_id.to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    };
+
+    // process building information
+    
+    let common_building_table_header = "id,builder,x,y,z,level,max_health,health,sapped,team,angle,building".to_owned();
+    let sentry_table_header = common_building_table_header.clone() + ",player_controlled,target,shells,rockets,is_mini";
+    let dispenser_table_header = common_building_table_header.clone() + ",metal";
+    let teleporter_table_header = common_building_table_header.clone() + ",is_entrance,connected_to,recharge_time,recharge_duration,times_used,yaw_to_exit";
+
+    let mut sentry_data: String = "".to_owned();
+    let mut dispenser_data: String = "".to_owned();
+    let mut teleporter_data: String = "".to_owned();
+
+    for (entity_id, building_data) in game_state.buildings {
+        let mut common = "".to_owned();
+        let mut specific = "".to_owned();
+
+        let builder = steam_id(building_data.builder());
+
+        let s: String = format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            entity_id,
+            builder,
+            building_data.position().x,
+            building_data.position().y,
+            building_data.position().z,
+            building_data.level(),
+            building_data.max_health(),
+            building_data.health(),
+            building_data.sapped(),
+            building_data.team(),
+            building_data.angle(),
+            building_data.building(),
+        );
+
+        common = common + &s;
+        
+        if building_data.class() == BuildingClass::Sentry
+        {
+            let target = steam_id(building_data.auto_aim_target());
+
+            let s: String = format!(
+                ",{},{},{},{},{}\n",
+                building_data.player_controlled(),
+                target,
+                building_data.shells(),
+                building_data.rockets(),
+                building_data.is_mini(),
+            );
+            specific = specific + &s;
+ 
+            common = common + &specific;
+            sentry_data = sentry_data + &common;
+        }
+        else if building_data.class() == BuildingClass::Dispenser
+        {
+            let s: String = format!(
+                ",{}\n",
+                building_data.metal(),
+            );
+            specific = specific + &s;
+   
+            common = common + &specific;
+            dispenser_data = dispenser_data + &common;
+        }
+        else if building_data.class() == BuildingClass::Teleporter
+        {
+            let s: String = format!(
+                ",{},{},{},{},{},{}\n",
+                building_data.is_entrance(),
+                building_data.other_end(),
+                building_data.recharge_time(),
+                building_data.recharge_duration(),
+                building_data.times_used(),
+                building_data.yaw_to_exit(),
+            );
+            specific = specific + &s;
+        
+            common = common + &specific;
+            teleporter_data = teleporter_data + &common;
+        }
+    }
+
+    let kills_table_header = "tick,attacker,assister,victim,weapon";
+    let mut kill_data: String = "".to_owned();
+    for kill in game_state.kills {
+        let killer = steam_id(UserId::from(kill.attacker_id));
+        let assister = steam_id(UserId::from(kill.assister_id));
+        let killed = steam_id(UserId::from(kill.victim_id));
+
+        let s: String = format!(
+            "{},{},{},{},{}\n",
+            kill.tick,
+            killer,
+            assister,
+            killed,
+            kill.weapon,
+        );
+        kill_data = kill_data + &s;
+    }
+
+
+    let rounds_table_header = "end_tick,length,winner";
+    let mut rounds_data: String = "".to_owned();
+    for round in server_state.rounds {
+        let s: String = format!(
+            "{},{},{}\n",
+            round.end_tick,
+            round.length,
+            round.winner,
+        );
+        rounds_data = rounds_data + &s;
+    }
+
+    Ok(format!("{:?}", header) + "\n[=============]\n" + 
+        scoreboard_table_header + "\n" + 
+        &scoreboard_data  + "[=============]\n" +  
+        &sentry_table_header + "\n" + 
+        &sentry_data  + "[=============]\n" +  
+        &dispenser_table_header + "\n" + 
+        &dispenser_data  + "[=============]\n" +  
+        &teleporter_table_header + "\n" + 
+        &teleporter_data  + "[=============]\n" +  
+        kills_table_header + "\n" + 
+        &kill_data  + "[=============]\n" +  
+        rounds_table_header + "\n" + 
+        &rounds_data)
+}
+
+/// A Python module implemented in Rust. The name of this function must match
+/// the `lib.name` setting in the `Cargo.toml`, else Python will not be able to
+/// import the module.
+#[pymodule]
+#[pyo3(name = "tf_demo_parser")]
+fn tf_demo_parser(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(py_main, m)?)?;
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_partial, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_many, m)?)?;
+    m.add_function(wrap_pyfunction!(ticker::ticks, m)?)?;
+    m.add_class::<ticker::PyTickIterator>()?;
+    types::register(m)?;
+    error::register(py, m)?;
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use crate::demo::parser::DemoTicker;
+use crate::python::error::DemoError;
+use crate::python::types::{PyHeader, PyTick};
+use crate::{Demo, DemoParser, GameStateAnalyser};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::fs;
+
+/// Iterator that steps trough a demo, yielding a snapshot of the game state every `interval` ticks
+#[pyclass(name = "TickIterator", module = "tf_demo_parser", unsendable)]
+pub struct PyTickIterator {
+    ticker: DemoTicker<'static, GameStateAnalyser>,
+    header: PyHeader,
+    interval: u32,
+    next_tick: u32,
+    last_tick: u32,
+}
+
+#[pymethods]
+impl PyTickIterator {
+    #[getter]
+    fn header(&self) -> PyHeader {
+        self.header.clone()
+    }
+
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> PyResult<Option<PyTick>> {
+        loop {
+            let pos = self.ticker.pos();
+            let tick = match self.ticker.next() {
+                Ok(Some(tick)) => tick,
+                Ok(None) => break,
+                Err(e) => {
+                    return Err(DemoError::new(e, self.ticker.demo_tick(), pos).into());
+                }
+            };
+            let tick_number = u32::from(tick.tick);
+            // the signon packets use the server tick, the demo ticks restart from 0 after that
+            if tick_number < self.last_tick {
+                self.next_tick = tick_number;
+            }
+            self.last_tick = tick_number;
+            if tick_number >= self.next_tick {
+                self.next_tick = tick_number.saturating_add(self.interval);
+                return Ok(Some(PyTick::new(tick_number, tick.state)));
+            }
+        }
+        if self.ticker.incomplete() {
+            let tick = self.ticker.demo_tick();
+            return Err(DemoError::truncated(tick, self.ticker.pos()).into());
+        }
+        Ok(None)
+    }
+}
+
+/// Step trough a demo file, yielding a `Tick` with the players and buildings every `interval` ticks
+///
+/// Only the "game_state" analyser is currently supported
+#[pyfunction(interval = "1", analyser = "\"game_state\"")]
+#[pyo3(text_signature = "(path, interval=1, analyser=\"game_state\")")]
+pub fn ticks(path: String, interval: u32, analyser: &str) -> PyResult<PyTickIterator> {
+    if analyser != "game_state" {
+        return Err(PyValueError::new_err(format!(
+            "Unsupported analyser for ticks: {}",
+            analyser
+        )));
+    }
+
+    let demo = Demo::owned(fs::read(path)?);
+    let parser = DemoParser::new_all_with_analyser(demo.get_stream(), GameStateAnalyser::new());
+    let (header, ticker) = parser.ticker()?;
+
+    Ok(PyTickIterator {
+        ticker,
+        header: PyHeader::from(&header),
+        interval: interval.max(1),
+        next_tick: 0,
+        last_tick: 0,
+    })
+}
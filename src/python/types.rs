@@ -0,0 +1,1009 @@
+use crate::demo::header::Header;
+use crate::demo::parser::analyser::{ChatMessage, Death, MatchState, Round, UserId, UserInfo};
+use crate::demo::parser::gamestateanalyser::{
+    Building, BuildingClass, CartStall, CartUpdate, CheckpointCapture, GameState, Kill,
+    KillSnapshot, PayloadRound, Player, PlayerState,
+};
+use crate::demo::parser::player_summary_analyzer::{
+    PlayerSummary, PlayerSummaryState, RoundSummary,
+};
+use crate::demo::vector::Vector;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Convert any of the python wrapper types into a (nested) python dict trough their serde representation
+fn to_py_dict<T: Serialize>(py: Python, value: &T) -> PyResult<PyObject> {
+    let value = serde_json::to_value(value)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+    json_to_py(py, &value)
+}
+
+fn json_to_py(py: Python, value: &Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Bool(value) => value.into_py(py),
+        Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+            (Some(int), _) => int.into_py(py),
+            (None, Some(int)) => int.into_py(py),
+            _ => number.as_f64().unwrap_or_default().into_py(py),
+        },
+        Value::String(string) => string.into_py(py),
+        Value::Array(values) => {
+            let list = PyList::empty(py);
+            for value in values {
+                list.append(json_to_py(py, value)?)?;
+            }
+            list.into_py(py)
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map {
+                dict.set_item(key, json_to_py(py, value)?)?;
+            }
+            dict.into_py(py)
+        }
+    })
+}
+
+fn position(vector: Vector) -> (f32, f32, f32) {
+    (vector.x, vector.y, vector.z)
+}
+
+macro_rules! py_methods {
+    ($ty:ident) => {
+        #[pymethods]
+        impl $ty {
+            fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+                to_py_dict(py, self)
+            }
+
+            fn __repr__(&self) -> String {
+                format!("{:?}", self)
+            }
+        }
+    };
+}
+
+#[pyclass(name = "Header", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyHeader {
+    #[pyo3(get)]
+    pub demo_type: String,
+    #[pyo3(get)]
+    pub version: u32,
+    #[pyo3(get)]
+    pub protocol: u32,
+    #[pyo3(get)]
+    pub server: String,
+    #[pyo3(get)]
+    pub nick: String,
+    #[pyo3(get)]
+    pub map: String,
+    #[pyo3(get)]
+    pub game: String,
+    #[pyo3(get)]
+    pub duration: f32,
+    #[pyo3(get)]
+    pub ticks: u32,
+    #[pyo3(get)]
+    pub frames: u32,
+    #[pyo3(get)]
+    pub signon: u32,
+}
+
+py_methods!(PyHeader);
+
+impl From<&Header> for PyHeader {
+    fn from(header: &Header) -> Self {
+        PyHeader {
+            demo_type: header.demo_type.clone(),
+            version: header.version,
+            protocol: header.protocol,
+            server: header.server.clone(),
+            nick: header.nick.clone(),
+            map: header.map.clone(),
+            game: header.game.clone(),
+            duration: header.duration,
+            ticks: header.ticks,
+            frames: header.frames,
+            signon: header.signon,
+        }
+    }
+}
+
+#[pyclass(name = "ChatMessage", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyChatMessage {
+    #[pyo3(get)]
+    pub kind: String,
+    #[pyo3(get, name = "from_")]
+    pub from: String,
+    #[pyo3(get)]
+    pub text: String,
+    #[pyo3(get)]
+    pub tick: u32,
+}
+
+py_methods!(PyChatMessage);
+
+impl From<&ChatMessage> for PyChatMessage {
+    fn from(message: &ChatMessage) -> Self {
+        PyChatMessage {
+            kind: serde_json::to_value(message.kind)
+                .ok()
+                .and_then(|kind| kind.as_str().map(String::from))
+                .unwrap_or_default(),
+            from: message.from.clone(),
+            text: message.text.clone(),
+            tick: message.tick.into(),
+        }
+    }
+}
+
+#[pyclass(name = "User", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyUser {
+    #[pyo3(get)]
+    pub user_id: u16,
+    #[pyo3(get)]
+    pub entity_id: u32,
+    #[pyo3(get)]
+    pub name: String,
+    /// The steam id as stored in the demo
+    #[pyo3(get)]
+    pub steam_id: String,
+    /// The 64 bit steam id, `None` for bots and SourceTV
+    #[pyo3(get)]
+    pub steam_id64: Option<u64>,
+    #[pyo3(get)]
+    pub team: String,
+    /// The classes played and the number of spawns on each class, sorted by spawn count
+    #[pyo3(get)]
+    pub classes: Vec<(String, u8)>,
+}
+
+py_methods!(PyUser);
+
+impl From<&UserInfo> for PyUser {
+    fn from(user: &UserInfo) -> Self {
+        PyUser {
+            user_id: user.user_id.into(),
+            entity_id: user.entity_id.into(),
+            name: user.name.clone(),
+            steam_id: user.steam_id.to_string(),
+            steam_id64: user.steam_id.steam_id64(),
+            team: user.team.to_string(),
+            classes: user
+                .classes
+                .sorted()
+                .map(|(class, count)| (class.to_string(), count))
+                .collect(),
+        }
+    }
+}
+
+#[pyclass(name = "Death", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyDeath {
+    #[pyo3(get)]
+    pub weapon: String,
+    #[pyo3(get)]
+    pub victim: u16,
+    #[pyo3(get)]
+    pub assister: Option<u16>,
+    #[pyo3(get)]
+    pub killer: u16,
+    #[pyo3(get)]
+    pub tick: u32,
+    #[pyo3(get)]
+    pub weapon_def_index: u32,
+    #[pyo3(get)]
+    pub custom_kill: u16,
+    /// One of "none", "mini" or "full"
+    #[pyo3(get)]
+    pub crit_type: String,
+    #[pyo3(get)]
+    pub death_flags: u16,
+    #[pyo3(get)]
+    pub penetrate_count: u16,
+    #[pyo3(get)]
+    pub headshot: bool,
+    #[pyo3(get)]
+    pub backstab: bool,
+    #[pyo3(get)]
+    pub domination: bool,
+    #[pyo3(get)]
+    pub revenge: bool,
+    #[pyo3(get)]
+    pub feign_death: bool,
+}
+
+py_methods!(PyDeath);
+
+impl From<&Death> for PyDeath {
+    fn from(death: &Death) -> Self {
+        PyDeath {
+            weapon: death.weapon.clone(),
+            victim: death.victim.into(),
+            assister: death.assister.map(u16::from),
+            killer: death.killer.into(),
+            tick: death.tick.into(),
+            weapon_def_index: death.weapon_def_index,
+            custom_kill: death.custom_kill,
+            crit_type: death.crit_type.to_string(),
+            death_flags: death.death_flags,
+            penetrate_count: death.penetrate_count,
+            headshot: death.is_headshot(),
+            backstab: death.is_backstab(),
+            domination: death.is_domination(),
+            revenge: death.is_revenge(),
+            feign_death: death.is_feign_death(),
+        }
+    }
+}
+
+#[pyclass(name = "Round", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyRound {
+    #[pyo3(get)]
+    pub winner: String,
+    #[pyo3(get)]
+    pub length: f32,
+    #[pyo3(get)]
+    pub end_tick: u32,
+}
+
+py_methods!(PyRound);
+
+impl From<&Round> for PyRound {
+    fn from(round: &Round) -> Self {
+        PyRound {
+            winner: round.winner.to_string(),
+            length: round.length,
+            end_tick: round.end_tick.into(),
+        }
+    }
+}
+
+#[pyclass(name = "MatchState", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyMatchState {
+    #[pyo3(get)]
+    pub chat: Vec<PyChatMessage>,
+    #[pyo3(get)]
+    pub users: Vec<PyUser>,
+    #[pyo3(get)]
+    pub deaths: Vec<PyDeath>,
+    #[pyo3(get)]
+    pub rounds: Vec<PyRound>,
+    #[pyo3(get)]
+    pub start_tick: u32,
+    #[pyo3(get)]
+    pub interval_per_tick: f32,
+}
+
+py_methods!(PyMatchState);
+
+impl From<&MatchState> for PyMatchState {
+    fn from(state: &MatchState) -> Self {
+        PyMatchState {
+            chat: state.chat.iter().map(PyChatMessage::from).collect(),
+            users: state.users.values().map(PyUser::from).collect(),
+            deaths: state.deaths.iter().map(PyDeath::from).collect(),
+            rounds: state.rounds.iter().map(PyRound::from).collect(),
+            start_tick: state.start_tick.into(),
+            interval_per_tick: state.interval_per_tick,
+        }
+    }
+}
+
+#[pyclass(name = "Player", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyPlayer {
+    #[pyo3(get)]
+    pub entity_id: u32,
+    #[pyo3(get)]
+    pub user_id: Option<u16>,
+    #[pyo3(get)]
+    pub name: Option<String>,
+    #[pyo3(get)]
+    pub steam_id: Option<String>,
+    #[pyo3(get)]
+    pub position: (f32, f32, f32),
+    #[pyo3(get)]
+    pub health: u16,
+    #[pyo3(get)]
+    pub max_health: u16,
+    #[pyo3(get)]
+    pub class: String,
+    #[pyo3(get)]
+    pub team: String,
+    #[pyo3(get)]
+    pub view_angle: f32,
+    #[pyo3(get)]
+    pub pitch_angle: f32,
+    #[pyo3(get)]
+    pub state: String,
+    #[pyo3(get)]
+    pub charge: u8,
+}
+
+py_methods!(PyPlayer);
+
+impl From<&Player> for PyPlayer {
+    fn from(player: &Player) -> Self {
+        PyPlayer {
+            entity_id: player.entity_id().into(),
+            user_id: player.info.as_ref().map(|info| info.user_id.into()),
+            name: player.info.as_ref().map(|info| info.name.clone()),
+            steam_id: player.info.as_ref().map(|info| info.steam_id.to_string()),
+            position: position(player.position),
+            health: player.health,
+            max_health: player.max_health,
+            class: player.class.to_string(),
+            team: player.team.to_string(),
+            view_angle: player.view_angle,
+            pitch_angle: player.pitch_angle,
+            state: match player.state {
+                PlayerState::Alive => "alive",
+                PlayerState::Dying => "dying",
+                PlayerState::Death => "death",
+                PlayerState::Respawnable => "respawnable",
+            }
+            .to_string(),
+            charge: player.charge,
+        }
+    }
+}
+
+/// A sentry, dispenser or teleporter, the building specific attributes are `None` for the other building types
+#[pyclass(name = "Building", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyBuilding {
+    #[pyo3(get)]
+    pub entity_id: u32,
+    #[pyo3(get)]
+    pub class: String,
+    #[pyo3(get)]
+    pub builder: u16,
+    #[pyo3(get)]
+    pub position: (f32, f32, f32),
+    #[pyo3(get)]
+    pub level: u8,
+    #[pyo3(get)]
+    pub max_health: u16,
+    #[pyo3(get)]
+    pub health: u16,
+    #[pyo3(get)]
+    pub building: bool,
+    #[pyo3(get)]
+    pub sapped: bool,
+    #[pyo3(get)]
+    pub team: String,
+    #[pyo3(get)]
+    pub angle: f32,
+    // sentry
+    #[pyo3(get)]
+    pub player_controlled: Option<bool>,
+    #[pyo3(get)]
+    pub auto_aim_target: Option<u16>,
+    #[pyo3(get)]
+    pub shells: Option<u16>,
+    #[pyo3(get)]
+    pub rockets: Option<u16>,
+    #[pyo3(get)]
+    pub is_mini: Option<bool>,
+    // dispenser
+    #[pyo3(get)]
+    pub metal: Option<u16>,
+    #[pyo3(get)]
+    pub healing: Option<Vec<u16>>,
+    // teleporter
+    #[pyo3(get)]
+    pub is_entrance: Option<bool>,
+    #[pyo3(get)]
+    pub other_end: Option<u32>,
+    #[pyo3(get)]
+    pub recharge_time: Option<f32>,
+    #[pyo3(get)]
+    pub recharge_duration: Option<f32>,
+    #[pyo3(get)]
+    pub times_used: Option<u16>,
+    #[pyo3(get)]
+    pub yaw_to_exit: Option<f32>,
+}
+
+py_methods!(PyBuilding);
+
+impl From<&Building> for PyBuilding {
+    fn from(building: &Building) -> Self {
+        let mut py_building = PyBuilding {
+            entity_id: building.entity_id().into(),
+            class: match building.class() {
+                BuildingClass::Sentry => "sentry",
+                BuildingClass::Dispenser => "dispenser",
+                BuildingClass::Teleporter => "teleporter",
+            }
+            .to_string(),
+            builder: building.builder().into(),
+            position: position(building.position()),
+            level: building.level(),
+            max_health: building.max_health(),
+            health: building.health(),
+            building: building.building(),
+            sapped: building.sapped(),
+            team: building.team().to_string(),
+            angle: building.angle(),
+            player_controlled: None,
+            auto_aim_target: None,
+            shells: None,
+            rockets: None,
+            is_mini: None,
+            metal: None,
+            healing: None,
+            is_entrance: None,
+            other_end: None,
+            recharge_time: None,
+            recharge_duration: None,
+            times_used: None,
+            yaw_to_exit: None,
+        };
+        match building {
+            Building::Sentry(sentry) => {
+                py_building.player_controlled = Some(sentry.player_controlled);
+                py_building.auto_aim_target = Some(sentry.auto_aim_target.into());
+                py_building.shells = Some(sentry.shells);
+                py_building.rockets = Some(sentry.rockets);
+                py_building.is_mini = Some(sentry.is_mini);
+            }
+            Building::Dispenser(dispenser) => {
+                py_building.metal = Some(dispenser.metal);
+                py_building.healing =
+                    Some(dispenser.healing.iter().copied().map(u16::from).collect());
+            }
+            Building::Teleporter(teleporter) => {
+                py_building.is_entrance = Some(teleporter.is_entrance);
+                py_building.other_end = Some(teleporter.other_end.into());
+                py_building.recharge_time = Some(teleporter.recharge_time);
+                py_building.recharge_duration = Some(teleporter.recharge_duration);
+                py_building.times_used = Some(teleporter.times_used);
+                py_building.yaw_to_exit = Some(teleporter.yaw_to_exit);
+            }
+        }
+        py_building
+    }
+}
+
+#[pyclass(name = "KillSnapshot", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyKillSnapshot {
+    #[pyo3(get)]
+    pub class: String,
+    #[pyo3(get)]
+    pub team: String,
+    #[pyo3(get)]
+    pub health: u16,
+    #[pyo3(get)]
+    pub position: (f32, f32, f32),
+    #[pyo3(get)]
+    pub view_angle: f32,
+    #[pyo3(get)]
+    pub pitch_angle: f32,
+}
+
+py_methods!(PyKillSnapshot);
+
+impl From<&KillSnapshot> for PyKillSnapshot {
+    fn from(snapshot: &KillSnapshot) -> Self {
+        PyKillSnapshot {
+            class: snapshot.class.to_string(),
+            team: snapshot.team.to_string(),
+            health: snapshot.health,
+            position: position(snapshot.position),
+            view_angle: snapshot.view_angle,
+            pitch_angle: snapshot.pitch_angle,
+        }
+    }
+}
+
+#[pyclass(name = "Kill", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyKill {
+    #[pyo3(get)]
+    pub attacker_id: u16,
+    #[pyo3(get)]
+    pub assister_id: u16,
+    #[pyo3(get)]
+    pub victim_id: u16,
+    #[pyo3(get)]
+    pub weapon: String,
+    #[pyo3(get)]
+    pub tick: u32,
+    #[pyo3(get)]
+    pub weapon_def_index: u32,
+    #[pyo3(get)]
+    pub custom_kill: u16,
+    /// One of "none", "mini" or "full"
+    #[pyo3(get)]
+    pub crit_type: String,
+    #[pyo3(get)]
+    pub death_flags: u16,
+    #[pyo3(get)]
+    pub penetrate_count: u16,
+    #[pyo3(get)]
+    pub headshot: bool,
+    #[pyo3(get)]
+    pub backstab: bool,
+    #[pyo3(get)]
+    pub domination: bool,
+    #[pyo3(get)]
+    pub revenge: bool,
+    #[pyo3(get)]
+    pub feign_death: bool,
+    #[pyo3(get)]
+    pub attacker: Option<PyKillSnapshot>,
+    #[pyo3(get)]
+    pub victim: Option<PyKillSnapshot>,
+    #[pyo3(get)]
+    pub distance: Option<f32>,
+}
+
+py_methods!(PyKill);
+
+impl From<&Kill> for PyKill {
+    fn from(kill: &Kill) -> Self {
+        PyKill {
+            attacker_id: kill.attacker_id,
+            assister_id: kill.assister_id,
+            victim_id: kill.victim_id,
+            weapon: kill.weapon.clone(),
+            tick: kill.tick.into(),
+            weapon_def_index: kill.weapon_def_index,
+            custom_kill: kill.custom_kill,
+            crit_type: kill.crit_type.to_string(),
+            death_flags: kill.death_flags,
+            penetrate_count: kill.penetrate_count,
+            headshot: kill.is_headshot(),
+            backstab: kill.is_backstab(),
+            domination: kill.is_domination(),
+            revenge: kill.is_revenge(),
+            feign_death: kill.is_feign_death(),
+            attacker: kill.attacker.as_ref().map(PyKillSnapshot::from),
+            victim: kill.victim.as_ref().map(PyKillSnapshot::from),
+            distance: kill.distance,
+        }
+    }
+}
+
+#[pyclass(name = "CartUpdate", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyCartUpdate {
+    #[pyo3(get)]
+    pub tick: u32,
+    #[pyo3(get)]
+    pub team: String,
+    #[pyo3(get)]
+    pub position: (f32, f32, f32),
+    #[pyo3(get)]
+    pub progress: f32,
+    #[pyo3(get)]
+    pub speed_level: i32,
+    /// -1 when the cart is blocked
+    #[pyo3(get)]
+    pub cappers: i32,
+    #[pyo3(get)]
+    pub pushers: Vec<u16>,
+}
+
+py_methods!(PyCartUpdate);
+
+impl From<&CartUpdate> for PyCartUpdate {
+    fn from(update: &CartUpdate) -> Self {
+        PyCartUpdate {
+            tick: update.tick.into(),
+            team: update.team.to_string(),
+            position: position(update.position),
+            progress: update.progress,
+            speed_level: update.speed_level,
+            cappers: update.cappers,
+            pushers: update.pushers.iter().copied().map(u16::from).collect(),
+        }
+    }
+}
+
+#[pyclass(name = "CartStall", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyCartStall {
+    #[pyo3(get)]
+    pub team: String,
+    #[pyo3(get)]
+    pub start_tick: u32,
+    #[pyo3(get)]
+    pub end_tick: Option<u32>,
+}
+
+py_methods!(PyCartStall);
+
+impl From<&CartStall> for PyCartStall {
+    fn from(stall: &CartStall) -> Self {
+        PyCartStall {
+            team: stall.team.to_string(),
+            start_tick: stall.start_tick.into(),
+            end_tick: stall.end_tick.map(u32::from),
+        }
+    }
+}
+
+#[pyclass(name = "CheckpointCapture", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyCheckpointCapture {
+    #[pyo3(get)]
+    pub tick: u32,
+    #[pyo3(get)]
+    pub point: u8,
+    #[pyo3(get)]
+    pub point_name: String,
+    #[pyo3(get)]
+    pub team: String,
+    #[pyo3(get)]
+    pub cappers: Vec<u16>,
+}
+
+py_methods!(PyCheckpointCapture);
+
+impl From<&CheckpointCapture> for PyCheckpointCapture {
+    fn from(capture: &CheckpointCapture) -> Self {
+        PyCheckpointCapture {
+            tick: capture.tick.into(),
+            point: capture.point,
+            point_name: capture.point_name.clone(),
+            team: capture.team.to_string(),
+            cappers: capture.cappers.iter().copied().map(u16::from).collect(),
+        }
+    }
+}
+
+#[pyclass(name = "PayloadRound", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyPayloadRound {
+    #[pyo3(get)]
+    pub start_tick: u32,
+    #[pyo3(get)]
+    pub updates: Vec<PyCartUpdate>,
+    #[pyo3(get)]
+    pub stalls: Vec<PyCartStall>,
+    #[pyo3(get)]
+    pub checkpoints: Vec<PyCheckpointCapture>,
+}
+
+py_methods!(PyPayloadRound);
+
+impl From<&PayloadRound> for PyPayloadRound {
+    fn from(round: &PayloadRound) -> Self {
+        PyPayloadRound {
+            start_tick: round.start_tick.into(),
+            updates: round.updates.iter().map(PyCartUpdate::from).collect(),
+            stalls: round.stalls.iter().map(PyCartStall::from).collect(),
+            checkpoints: round
+                .checkpoints
+                .iter()
+                .map(PyCheckpointCapture::from)
+                .collect(),
+        }
+    }
+}
+
+#[pyclass(name = "GameState", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyGameState {
+    #[pyo3(get)]
+    pub players: Vec<PyPlayer>,
+    #[pyo3(get)]
+    pub buildings: Vec<PyBuilding>,
+    #[pyo3(get)]
+    pub kills: Vec<PyKill>,
+    /// The minimum corner of the map boundary
+    #[pyo3(get)]
+    pub world_min: Option<(f32, f32, f32)>,
+    /// The maximum corner of the map boundary
+    #[pyo3(get)]
+    pub world_max: Option<(f32, f32, f32)>,
+    #[pyo3(get)]
+    pub tick: u32,
+    /// The cart progress of every round, empty for non payload maps
+    #[pyo3(get)]
+    pub payload: Vec<PyPayloadRound>,
+}
+
+py_methods!(PyGameState);
+
+impl From<&GameState> for PyGameState {
+    fn from(state: &GameState) -> Self {
+        PyGameState {
+            players: state.players.iter().map(PyPlayer::from).collect(),
+            buildings: state.buildings.values().map(PyBuilding::from).collect(),
+            kills: state.kills.iter().map(PyKill::from).collect(),
+            world_min: state
+                .world
+                .as_ref()
+                .map(|world| position(world.boundary_min)),
+            world_max: state
+                .world
+                .as_ref()
+                .map(|world| position(world.boundary_max)),
+            tick: state.tick.into(),
+            payload: state.payload.iter().map(PyPayloadRound::from).collect(),
+        }
+    }
+}
+
+#[pyclass(name = "PlayerSummary", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyPlayerSummary {
+    #[pyo3(get)]
+    pub user_id: u16,
+    #[pyo3(get)]
+    pub points: u32,
+    #[pyo3(get)]
+    pub kills: u32,
+    #[pyo3(get)]
+    pub assists: u32,
+    #[pyo3(get)]
+    pub deaths: u32,
+    #[pyo3(get)]
+    pub buildings_destroyed: u32,
+    #[pyo3(get)]
+    pub captures: u32,
+    #[pyo3(get)]
+    pub defenses: u32,
+    #[pyo3(get)]
+    pub dominations: u32,
+    #[pyo3(get)]
+    pub revenges: u32,
+    #[pyo3(get)]
+    pub ubercharges: u32,
+    #[pyo3(get)]
+    pub headshots: u32,
+    #[pyo3(get)]
+    pub teleports: u32,
+    #[pyo3(get)]
+    pub healing: u32,
+    #[pyo3(get)]
+    pub backstabs: u32,
+    #[pyo3(get)]
+    pub bonus_points: u32,
+    #[pyo3(get)]
+    pub support: u32,
+    #[pyo3(get)]
+    pub damage_dealt: u32,
+    #[pyo3(get)]
+    pub suicides: u32,
+    #[pyo3(get)]
+    pub buildings_built: u32,
+    #[pyo3(get)]
+    pub crits: u32,
+    #[pyo3(get)]
+    pub resupply_points: u32,
+    #[pyo3(get)]
+    pub score: u32,
+    #[pyo3(get)]
+    pub total_score: u32,
+    #[pyo3(get)]
+    pub ping: u32,
+    #[pyo3(get)]
+    pub scoreboard_damage: u32,
+    #[pyo3(get)]
+    pub scoreboard_healing: u32,
+    #[pyo3(get)]
+    pub healing_assist: u32,
+    #[pyo3(get)]
+    pub damage_assist: u32,
+    #[pyo3(get)]
+    pub max_buffed_health: u32,
+}
+
+py_methods!(PyPlayerSummary);
+
+impl PyPlayerSummary {
+    fn new(user_id: UserId, summary: &PlayerSummary) -> Self {
+        PyPlayerSummary {
+            user_id: user_id.into(),
+            points: summary.points,
+            kills: summary.kills,
+            assists: summary.assists,
+            deaths: summary.deaths,
+            buildings_destroyed: summary.buildings_destroyed,
+            captures: summary.captures,
+            defenses: summary.defenses,
+            dominations: summary.dominations,
+            revenges: summary.revenges,
+            ubercharges: summary.ubercharges,
+            headshots: summary.headshots,
+            teleports: summary.teleports,
+            healing: summary.healing,
+            backstabs: summary.backstabs,
+            bonus_points: summary.bonus_points,
+            support: summary.support,
+            damage_dealt: summary.damage_dealt,
+            suicides: summary.suicides,
+            buildings_built: summary.buildings_built,
+            crits: summary.crits,
+            resupply_points: summary.resupply_points,
+            score: summary.scoreboard.score,
+            total_score: summary.scoreboard.total_score,
+            ping: summary.scoreboard.ping,
+            scoreboard_damage: summary.scoreboard.damage,
+            scoreboard_healing: summary.scoreboard.healing,
+            healing_assist: summary.scoreboard.healing_assist,
+            damage_assist: summary.scoreboard.damage_assist,
+            max_buffed_health: summary.scoreboard.max_buffed_health,
+        }
+    }
+}
+
+fn py_player_summaries(summaries: &HashMap<UserId, PlayerSummary>) -> Vec<PyPlayerSummary> {
+    let mut player_summaries: Vec<_> = summaries
+        .iter()
+        .map(|(user_id, summary)| PyPlayerSummary::new(*user_id, summary))
+        .collect();
+    player_summaries.sort_by_key(|summary| summary.user_id);
+    player_summaries
+}
+
+#[pyclass(name = "RoundSummary", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyRoundSummary {
+    #[pyo3(get)]
+    pub start_tick: u32,
+    #[pyo3(get)]
+    pub end_tick: u32,
+    #[pyo3(get)]
+    pub duration: f32,
+    #[pyo3(get)]
+    pub winner: Option<String>,
+    #[pyo3(get)]
+    pub player_summaries: Vec<PyPlayerSummary>,
+}
+
+py_methods!(PyRoundSummary);
+
+impl From<&RoundSummary> for PyRoundSummary {
+    fn from(round: &RoundSummary) -> Self {
+        PyRoundSummary {
+            start_tick: round.start_tick.into(),
+            end_tick: round.end_tick.into(),
+            duration: round.duration,
+            winner: round.winner.map(|team| team.to_string()),
+            player_summaries: py_player_summaries(&round.player_summaries),
+        }
+    }
+}
+
+#[pyclass(name = "PlayerSummaryState", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyPlayerSummaryState {
+    #[pyo3(get)]
+    pub player_summaries: Vec<PyPlayerSummary>,
+    #[pyo3(get)]
+    pub users: Vec<PyUser>,
+    #[pyo3(get)]
+    pub rounds: Vec<PyRoundSummary>,
+}
+
+py_methods!(PyPlayerSummaryState);
+
+impl From<&PlayerSummaryState> for PyPlayerSummaryState {
+    fn from(state: &PlayerSummaryState) -> Self {
+        PyPlayerSummaryState {
+            player_summaries: py_player_summaries(&state.player_summaries),
+            users: state.users.values().map(PyUser::from).collect(),
+            rounds: state.rounds.iter().map(PyRoundSummary::from).collect(),
+        }
+    }
+}
+
+/// The result of parsing a single demo with `parse_many`
+///
+/// Only the outputs of the requested analysers are set, if the demo failed to parse `error` contains the reason
+#[pyclass(name = "ParseResult", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyParseResult {
+    #[pyo3(get)]
+    pub path: String,
+    #[pyo3(get)]
+    pub header: Option<PyHeader>,
+    #[pyo3(get)]
+    pub match_state: Option<PyMatchState>,
+    #[pyo3(get)]
+    pub game_state: Option<PyGameState>,
+    #[pyo3(get)]
+    pub player_summaries: Option<PyPlayerSummaryState>,
+    /// The exception that stopped the parser, if any
+    #[pyo3(get)]
+    #[serde(serialize_with = "serialize_exception")]
+    pub error: Option<PyObject>,
+}
+
+py_methods!(PyParseResult);
+
+/// Information about how far a demo was parsed
+#[pyclass(name = "ParseDiagnostics", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyParseDiagnostics {
+    #[pyo3(get)]
+    pub incomplete: bool,
+    #[pyo3(get)]
+    pub last_tick: u32,
+    #[pyo3(get)]
+    pub byte_offset: usize,
+    /// The exception that stopped the parser, if any
+    #[pyo3(get)]
+    #[serde(serialize_with = "serialize_exception")]
+    pub error: Option<PyObject>,
+}
+
+py_methods!(PyParseDiagnostics);
+
+fn serialize_exception<S: Serializer>(
+    error: &Option<PyObject>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    Python::with_gil(|py| {
+        error
+            .as_ref()
+            .map(|error| error.as_ref(py).to_string())
+            .serialize(serializer)
+    })
+}
+
+pub fn register(m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyHeader>()?;
+    m.add_class::<PyChatMessage>()?;
+    m.add_class::<PyUser>()?;
+    m.add_class::<PyDeath>()?;
+    m.add_class::<PyRound>()?;
+    m.add_class::<PyMatchState>()?;
+    m.add_class::<PyPlayer>()?;
+    m.add_class::<PyBuilding>()?;
+    m.add_class::<PyKillSnapshot>()?;
+    m.add_class::<PyKill>()?;
+    m.add_class::<PyCartUpdate>()?;
+    m.add_class::<PyCartStall>()?;
+    m.add_class::<PyCheckpointCapture>()?;
+    m.add_class::<PyPayloadRound>()?;
+    m.add_class::<PyGameState>()?;
+    m.add_class::<PyPlayerSummary>()?;
+    m.add_class::<PyRoundSummary>()?;
+    m.add_class::<PyPlayerSummaryState>()?;
+    m.add_class::<PyTick>()?;
+    m.add_class::<PyParseResult>()?;
+    m.add_class::<PyParseDiagnostics>()?;
+    Ok(())
+}
+
+/// The players and buildings at a single tick of the demo
+#[pyclass(name = "Tick", module = "tf_demo_parser")]
+#[derive(Debug, Clone, Serialize)]
+pub struct PyTick {
+    #[pyo3(get)]
+    pub tick: u32,
+    #[pyo3(get)]
+    pub players: Vec<PyPlayer>,
+    #[pyo3(get)]
+    pub buildings: Vec<PyBuilding>,
+}
+
+py_methods!(PyTick);
+
+impl PyTick {
+    pub fn new(tick: u32, state: &GameState) -> Self {
+        PyTick {
+            tick,
+            players: state.players.iter().map(PyPlayer::from).collect(),
+            buildings: state.buildings.values().map(PyBuilding::from).collect(),
+        }
+    }
+}
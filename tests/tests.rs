@@ -1,8 +1,26 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::fs;
 use test_case::test_case;
 
+use tf_demo_parser::demo::cut::cut_demo;
+use tf_demo_parser::demo::data::DemoTick;
+use tf_demo_parser::demo::merge::merge_demos;
+use tf_demo_parser::demo::message::packetentities::PacketEntity;
+use tf_demo_parser::demo::message::Message;
+use tf_demo_parser::demo::packet::datatable::{ParseSendTable, ServerClass};
+use tf_demo_parser::demo::packet::message::MessagePacketMeta;
+use tf_demo_parser::demo::packet::stringtable::StringTableEntry;
 use tf_demo_parser::demo::parser::gamestateanalyser::{GameState, GameStateAnalyser};
-use tf_demo_parser::{Demo, DemoParser, MatchState};
+use tf_demo_parser::demo::parser::handler::BorrowMessageHandler;
+use tf_demo_parser::demo::parser::lifeanalyser::LifeAnalyser;
+use tf_demo_parser::demo::parser::objectiveanalyser::ObjectiveAnalyser;
+use tf_demo_parser::demo::parser::player_summary_analyzer::PlayerSummaryAnalyzer;
+use tf_demo_parser::demo::parser::{DemoIndex, MessageHandler, ParserCheckpoint};
+use tf_demo_parser::demo::sendprop::{SendPropIdentifier, SendPropValue};
+use tf_demo_parser::{
+    parse_many, Analyser, Demo, DemoParser, MatchState, MessageType, ParseError, ParserState,
+};
 
 #[test_case("small.dem", "small.json"; "small.dem")]
 #[test_case("gully.dem", "gully.json"; "gully.dem")]
@@ -61,3 +79,375 @@ fn game_state_test(input_file: &str, snapshot_file: &str) {
     pretty_assertions::assert_eq!(expected.players, state.players);
     pretty_assertions::assert_eq!(expected, state);
 }
+
+/// Compare the output of an analyser for `small.dem` against a snapshot
+fn analyser_snapshot_test<A>(analyser: A, snapshot_file: &str)
+where
+    A: MessageHandler,
+    A::Output: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug,
+{
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let demo = Demo::new(&file);
+    let (_, state) = DemoParser::new_with_analyser(demo.get_stream(), analyser)
+        .parse()
+        .unwrap();
+
+    // fs::write(
+    //     format!("test_data/{}", snapshot_file),
+    //     serde_json::to_string_pretty(&state).unwrap(),
+    // )
+    // .unwrap();
+
+    let expected: A::Output = serde_json::from_slice(
+        fs::read(format!("test_data/{}", snapshot_file))
+            .expect("Unable to read file")
+            .as_slice(),
+    )
+    .unwrap();
+    pretty_assertions::assert_eq!(expected, state);
+}
+
+#[test]
+fn life_test() {
+    analyser_snapshot_test(LifeAnalyser::new(), "small_life.json");
+}
+
+#[test]
+fn objective_test() {
+    analyser_snapshot_test(ObjectiveAnalyser::new(), "small_objective.json");
+}
+
+#[test]
+fn player_summary_test() {
+    analyser_snapshot_test(PlayerSummaryAnalyzer::new(), "small_player_summary.json");
+}
+
+#[test_case("small.dem"; "small.dem")]
+#[test_case("gully.dem"; "gully.dem")]
+fn combined_analyser_test(input_file: &str) {
+    let file = fs::read(format!("test_data/{}", input_file)).expect("Unable to read file");
+    let demo = Demo::new(&file);
+    let (_, match_state) = DemoParser::new_all(demo.get_stream()).parse().unwrap();
+    let (_, game_state) =
+        DemoParser::new_all_with_analyser(demo.get_stream(), GameStateAnalyser::new())
+            .parse()
+            .unwrap();
+
+    let (_, (combined_match_state, combined_game_state)) = DemoParser::new_all_with_analyser(
+        demo.get_stream(),
+        (Analyser::new(), GameStateAnalyser::new()),
+    )
+    .parse()
+    .unwrap();
+
+    pretty_assertions::assert_eq!(match_state, combined_match_state);
+    pretty_assertions::assert_eq!(game_state, combined_game_state);
+}
+
+#[test]
+fn parse_many_test() {
+    let results = parse_many(
+        &["test_data/small.dem", "test_data/missing.dem"],
+        Analyser::new,
+        true,
+    );
+    assert_eq!(2, results.len());
+
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let demo = Demo::new(&file);
+    let (_, expected) = DemoParser::new_all(demo.get_stream()).parse().unwrap();
+    let (_, state) = results[0].as_ref().unwrap();
+    pretty_assertions::assert_eq!(&expected, state);
+
+    assert!(matches!(results[1], Err(ParseError::Io(_))));
+}
+
+#[test]
+fn parse_partial_test() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+
+    let demo = Demo::new(&file);
+    let (_, _, diagnostics) = DemoParser::new(demo.get_stream()).parse_partial().unwrap();
+    assert!(!diagnostics.incomplete);
+    assert!(diagnostics.error.is_none());
+    assert_eq!(file.len(), diagnostics.byte_offset);
+
+    let truncated = Demo::new(&file[0..690000]);
+    let (_, state, diagnostics) = DemoParser::new(truncated.get_stream())
+        .parse_partial()
+        .unwrap();
+    assert!(diagnostics.incomplete);
+    assert!(diagnostics.error.is_none());
+    assert_eq!(71, u32::from(diagnostics.last_tick));
+    assert!(diagnostics.byte_offset <= 690000);
+    assert_eq!(1, state.users.len());
+}
+
+#[test]
+fn demo_index_test() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let demo = Demo::new(&file);
+    let index = DemoIndex::new(
+        DemoParser::new_all_with_analyser(demo.get_stream(), GameStateAnalyser::new()),
+        20,
+    )
+    .unwrap();
+    let replay_index = DemoIndex::new(
+        DemoParser::new_all_with_analyser(demo.get_stream(), GameStateAnalyser::new()),
+        100_000,
+    )
+    .unwrap();
+
+    assert_eq!(Some(DemoTick::from(0)), index.ticks().next());
+    for tick in [0, 10, 45, 60, 99] {
+        let tick = DemoTick::from(tick);
+        let seeked = index.seek(tick).unwrap();
+        let replayed = replay_index.seek(tick).unwrap();
+        pretty_assertions::assert_eq!(replayed.state(), seeked.state());
+        assert!(seeked.demo_tick() <= tick);
+    }
+
+    let (_, expected) =
+        DemoParser::new_all_with_analyser(demo.get_stream(), GameStateAnalyser::new())
+            .parse()
+            .unwrap();
+    let end = index.seek(DemoTick::from(u32::MAX)).unwrap();
+    pretty_assertions::assert_eq!(&expected, end.state());
+}
+
+#[derive(Default, Clone)]
+struct EntityCollector {
+    entities: Vec<(DemoTick, PacketEntity)>,
+}
+
+impl MessageHandler for EntityCollector {
+    type Output = Vec<(DemoTick, PacketEntity)>;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        message_type == MessageType::PacketEntities
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, _parser_state: &ParserState) {
+        if let Message::PacketEntities(message) = message {
+            self.entities
+                .extend(message.entities.iter().map(|entity| (tick, entity.clone())));
+        }
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self::Output {
+        self.entities
+    }
+}
+
+#[test]
+fn checkpoint_resume_test() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let demo = Demo::new(&file);
+    let (_, mut ticker) =
+        DemoParser::new_with_analyser(demo.get_stream(), EntityCollector::default())
+            .ticker()
+            .unwrap();
+    while ticker.demo_tick() != 50 {
+        ticker.tick().unwrap();
+    }
+    let checkpoint = ticker.checkpoint();
+    let checkpoint: ParserCheckpoint =
+        serde_json::from_str(&serde_json::to_string(&checkpoint).unwrap()).unwrap();
+    let before_checkpoint = ticker.clone().into_state().len();
+    while ticker.tick().unwrap() {}
+    let expected = ticker.into_state().split_off(before_checkpoint);
+
+    let mut resumed = DemoParser::new_with_analyser(demo.get_stream(), EntityCollector::default())
+        .resume(checkpoint)
+        .unwrap();
+    while resumed.tick().unwrap() {}
+    let entities = resumed.into_state();
+
+    assert!(!entities.is_empty());
+    pretty_assertions::assert_eq!(expected, entities);
+}
+
+/// Compares the entity store against the players tracked by the game state analyser
+#[derive(Default)]
+struct EntityStoreChecker {
+    game_state: GameStateAnalyser,
+    checked_players: usize,
+    full_updates: usize,
+}
+
+impl MessageHandler for EntityStoreChecker {
+    type Output = (usize, usize);
+
+    fn does_handle(message_type: MessageType) -> bool {
+        GameStateAnalyser::does_handle(message_type)
+    }
+
+    fn handle_message(&mut self, message: &Message, tick: DemoTick, parser_state: &ParserState) {
+        self.game_state.handle_message(message, tick, parser_state);
+
+        let Message::PacketEntities(message) = message else {
+            return;
+        };
+        // the store is updated before the message is passed to the analyser
+        let store = parser_state.entity_store.as_ref().unwrap();
+        if message.delta.is_none() {
+            assert_eq!(message.entities.len(), store.len());
+            self.full_updates += 1;
+        }
+
+        let health = SendPropIdentifier::new("DT_BasePlayer", "m_iHealth");
+        for player in &self.game_state.borrow_output(parser_state).players {
+            let entity = store.get(player.entity_id()).unwrap();
+            assert_eq!(
+                Some(&SendPropValue::Integer(player.health as i64)),
+                entity.get_prop(health)
+            );
+            self.checked_players += 1;
+        }
+    }
+
+    fn handle_string_entry(
+        &mut self,
+        table: &str,
+        index: usize,
+        entry: &StringTableEntry,
+        parser_state: &ParserState,
+    ) {
+        self.game_state
+            .handle_string_entry(table, index, entry, parser_state);
+    }
+
+    fn handle_data_tables(
+        &mut self,
+        tables: &[ParseSendTable],
+        server_classes: &[ServerClass],
+        parser_state: &ParserState,
+    ) {
+        self.game_state
+            .handle_data_tables(tables, server_classes, parser_state);
+    }
+
+    fn handle_packet_meta(
+        &mut self,
+        tick: DemoTick,
+        meta: &MessagePacketMeta,
+        parser_state: &ParserState,
+    ) {
+        self.game_state.handle_packet_meta(tick, meta, parser_state);
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self::Output {
+        (self.checked_players, self.full_updates)
+    }
+}
+
+#[test]
+fn entity_store_test() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let demo = Demo::new(&file);
+    let (_, (checked_players, full_updates)) =
+        DemoParser::new_with_analyser(demo.get_stream(), EntityStoreChecker::default())
+            .with_entity_store()
+            .parse()
+            .unwrap();
+    assert!(checked_players > 0);
+    assert!(full_updates > 0);
+}
+
+#[test]
+fn cut_demo_test() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let demo = Demo::new(&file);
+    let cut = cut_demo(demo.get_stream(), DemoTick::from(40), DemoTick::from(80)).unwrap();
+
+    assert!(matches!(
+        cut_demo(demo.get_stream(), DemoTick::from(80), DemoTick::from(40)),
+        Err(ParseError::InvalidDemo(_))
+    ));
+    assert!(matches!(
+        cut_demo(
+            demo.get_stream(),
+            DemoTick::from(1_000_000),
+            DemoTick::from(2_000_000)
+        ),
+        Err(ParseError::InvalidDemo(_))
+    ));
+
+    let cut_demo = Demo::new(&cut);
+    let (header, state) =
+        DemoParser::new_all_with_analyser(cut_demo.get_stream(), GameStateAnalyser::new())
+            .parse()
+            .unwrap();
+    assert_eq!(40, header.ticks);
+    assert!(!state.players.is_empty());
+    assert!(header.duration > 0.0);
+
+    let index = DemoIndex::new(
+        DemoParser::new_all_with_analyser(demo.get_stream(), GameStateAnalyser::new()),
+        20,
+    )
+    .unwrap();
+    let expected = index.seek(DemoTick::from(80)).unwrap();
+    pretty_assertions::assert_eq!(expected.state().players, state.players);
+    pretty_assertions::assert_eq!(expected.state().buildings, state.buildings);
+    pretty_assertions::assert_eq!(expected.state().world, state.world);
+}
+
+#[test]
+fn merge_demos_test() {
+    let file = fs::read("test_data/small.dem").expect("Unable to read file");
+    let demo = Demo::new(&file);
+    let (header, state) = DemoParser::new(demo.get_stream()).parse().unwrap();
+
+    let merged = merge_demos([demo.get_stream(), demo.get_stream()]).unwrap();
+    let merged_demo = Demo::new(&merged);
+    let (merged_header, merged_state) = DemoParser::new(merged_demo.get_stream()).parse().unwrap();
+
+    assert_eq!(header.ticks * 2 + 1, merged_header.ticks);
+    assert_eq!(header.map, merged_header.map);
+    // the signon of the second demo is skipped
+    assert!(merged.len() < file.len() * 2 - header.signon as usize);
+    assert_eq!(state.deaths.len() * 2, merged_state.deaths.len());
+    assert_eq!(state.chat.len() * 2, merged_state.chat.len());
+    assert_eq!(state.users, merged_state.users);
+
+    // every message of both demos except the signon of the second demo is kept,
+    // with the ticks of the second demo continuing after the first
+    let (_, ticks) = DemoParser::new_all_with_analyser(demo.get_stream(), MessageTicks::default())
+        .parse()
+        .unwrap();
+    let (_, merged_ticks) =
+        DemoParser::new_all_with_analyser(merged_demo.get_stream(), MessageTicks::default())
+            .parse()
+            .unwrap();
+    // the signon messages have the server tick from before recording started, the recorded ticks start at 0
+    let signon_messages = ticks.iter().position(|tick| *tick == 0).unwrap();
+    let offset = header.ticks + 1;
+    let expected_ticks: Vec<u32> = ticks
+        .iter()
+        .copied()
+        .chain(ticks[signon_messages..].iter().map(|tick| tick + offset))
+        .collect();
+    assert!(signon_messages > 0);
+    assert_eq!(expected_ticks, merged_ticks);
+}
+
+/// The tick of every message in the demo
+#[derive(Default)]
+struct MessageTicks(Vec<u32>);
+
+impl MessageHandler for MessageTicks {
+    type Output = Vec<u32>;
+
+    fn does_handle(_message_type: MessageType) -> bool {
+        true
+    }
+
+    fn handle_message(&mut self, _message: &Message, tick: DemoTick, _parser_state: &ParserState) {
+        self.0.push(tick.into());
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self::Output {
+        self.0
+    }
+}